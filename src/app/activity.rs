@@ -0,0 +1,214 @@
+//! Activity log: significant events (starts, kills, failures, configuration changes, ...) with
+//! their time and the VM they concern. It is appended to `{base_dir}activity.log` so that it
+//! survives the TUI, and the end of this file is read again on startup.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::vm::helpers::format_utc;
+
+/// Name of the activity log, in the base directory
+pub const ACTIVITY_LOG_FILE: &str = "activity.log";
+
+/// Number of entries kept in memory (the file keeps all of them until it is rotated)
+const MAX_ENTRIES: usize = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Error => "ERROR",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "INFO" => Some(Severity::Info),
+            "WARNING" => Some(Severity::Warning),
+            "ERROR" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+
+    /// Next minimum severity of the activity log filter (wrapping around)
+    #[must_use]
+    pub fn next(&self) -> Self {
+        match self {
+            Severity::Info => Severity::Warning,
+            Severity::Warning => Severity::Error,
+            Severity::Error => Severity::Info,
+        }
+    }
+}
+
+pub struct Entry {
+    /// UTC time, "YYYY-MM-DD HH:MM:SS"
+    pub time: String,
+    pub severity: Severity,
+    /// VM concerned by the entry, if any
+    pub vm_name: Option<String>,
+    pub message: String,
+}
+
+impl Entry {
+    /// Line of the entry in the activity log file, ie. "2025-01-01 12:00:00 INFO [myvm] Started"
+    fn to_line(&self) -> String {
+        format!(
+            "{} {} [{}] {}",
+            self.time,
+            self.severity.as_str(),
+            self.vm_name.as_deref().unwrap_or("-"),
+            self.message.replace('\n', " ")
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let time = line.get(..19)?;
+        let (severity, rest) = line.get(20..)?.split_once(' ')?;
+        let (vm_name, message) = rest.strip_prefix('[')?.split_once("] ")?;
+        Some(Entry {
+            time: time.to_owned(),
+            severity: Severity::parse(severity)?,
+            vm_name: (vm_name != "-").then(|| vm_name.to_owned()),
+            message: message.to_owned(),
+        })
+    }
+}
+
+pub struct ActivityLog {
+    pub path: PathBuf,
+    /// Entries sorted by time, the most recent being the last one
+    pub entries: Vec<Entry>,
+    /// Set when the activity log file can't be written
+    pub write_error: Option<String>,
+}
+
+impl ActivityLog {
+    /// Reads the previous entries of the activity log, a missing file meaning there aren't any
+    pub fn open(base_dir: &str) -> Self {
+        let path = Path::new(base_dir).join(ACTIVITY_LOG_FILE);
+        let mut entries: Vec<Entry> = std::fs::read(&path)
+            .map(|data| {
+                String::from_utf8_lossy(&data)
+                    .lines()
+                    .filter_map(Entry::from_line)
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.drain(..entries.len().saturating_sub(MAX_ENTRIES));
+
+        ActivityLog {
+            path,
+            entries,
+            write_error: None,
+        }
+    }
+
+    /// Adds an entry, now, to the activity log and appends it to the file
+    pub fn record(&mut self, severity: Severity, vm_name: Option<&str>, message: &str) {
+        let entry = Entry {
+            time: format_utc(SystemTime::now()),
+            severity,
+            vm_name: vm_name.map(str::to_owned),
+            message: message.to_owned(),
+        };
+
+        self.write_error = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", entry.to_line()))
+            .err()
+            .map(|err| format!("Failed to write to {}: {err}", self.path.display()));
+
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn info(&mut self, vm_name: &str, message: &str) {
+        self.record(Severity::Info, Some(vm_name), message);
+    }
+
+    pub fn warning(&mut self, vm_name: &str, message: &str) {
+        self.record(Severity::Warning, Some(vm_name), message);
+    }
+
+    pub fn error(&mut self, vm_name: &str, message: &str) {
+        self.record(Severity::Error, Some(vm_name), message);
+    }
+
+    /// Entries concerning `vm_name` (all of them if `None`) with at least `min_severity`
+    pub fn filtered<'a>(
+        &'a self,
+        vm_name: Option<&'a str>,
+        min_severity: Severity,
+    ) -> impl Iterator<Item = &'a Entry> {
+        self.entries.iter().filter(move |entry| {
+            entry.severity >= min_severity
+                && vm_name.is_none_or(|vm_name| entry.vm_name.as_deref() == Some(vm_name))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_round_trip() {
+        let line = "2025-01-01 12:00:00 WARNING [myvm] Killed with [SIGKILL]";
+        let entry = Entry::from_line(line).unwrap();
+        assert_eq!(entry.time, "2025-01-01 12:00:00");
+        assert_eq!(entry.severity, Severity::Warning);
+        assert_eq!(entry.vm_name.as_deref(), Some("myvm"));
+        assert_eq!(entry.message, "Killed with [SIGKILL]");
+        assert_eq!(entry.to_line(), line);
+    }
+
+    #[test]
+    fn entry_without_vm() {
+        let line = "2025-01-01 12:00:00 INFO [-] tui.conf: unknown parameter 'foo' ignored";
+        let entry = Entry::from_line(line).unwrap();
+        assert_eq!(entry.vm_name, None);
+        assert_eq!(entry.to_line(), line);
+    }
+
+    #[test]
+    fn entry_message_on_one_line() {
+        let entry = Entry {
+            time: "2025-01-01 12:00:00".to_owned(),
+            severity: Severity::Error,
+            vm_name: None,
+            message: "Failed:\nno such file".to_owned(),
+        };
+        let entry = Entry::from_line(&entry.to_line()).unwrap();
+        assert_eq!(entry.message, "Failed: no such file");
+    }
+
+    #[test]
+    fn malformed_entries() {
+        for line in [
+            "",
+            "2025-01-01 12:00:00",
+            "2025-01-01 12:00:00 DEBUG [myvm] Started",
+            "2025-01-01 12:00:00 INFO myvm Started",
+            "2025-01-01 12:00:00 INFO [myvm Started",
+            "2025-01-01 12:00:00INFO [myvm] Started",
+            "2025-01-01 12:00:0é INFO [myvm] Started",
+        ] {
+            assert!(Entry::from_line(line).is_none(), "{line:?}");
+        }
+    }
+}
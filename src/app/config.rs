@@ -0,0 +1,149 @@
+use regex::Regex;
+use std::time::Duration;
+
+use crate::console;
+use crate::vm;
+
+/// Name of the TUI configuration file, in the base directory
+pub const CONFIG_FILE: &str = "tui.conf";
+
+/// TUI settings, read from `{base_dir}/tui.conf`.
+/// This file uses the same `key=value` format as the VMs configurations, every key is optional.
+pub struct Config {
+    /// Key used to leave the console and go back to the VMs list (ie. "ctrl-]")
+    pub console_escape: String,
+    /// Bytes sent by the terminal when `console_escape` is pressed
+    pub console_escape_bytes: Vec<u8>,
+    /// Key used to search the console (ie. "alt-/")
+    pub console_search: String,
+    /// Bytes sent by the terminal when `console_search` is pressed
+    pub console_search_bytes: Vec<u8>,
+    /// Key used to start/stop recording the console (ie. "alt-r")
+    pub console_record: String,
+    /// Bytes sent by the terminal when `console_record` is pressed
+    pub console_record_bytes: Vec<u8>,
+    /// Size (in bytes) above which the console logs and the activity log are rotated
+    pub log_max_size: u64,
+    /// Saved filters of the log viewer (name and regex), from the `filter_<name>=<regex>` parameters
+    pub filters: Vec<(String, Regex)>,
+    /// Interval between two samplings of the VMs statistics
+    pub sample_interval: Duration,
+    /// Duration of the resource usage history kept for each VM
+    pub history_window: Duration,
+    /// Starting a VM must be confirmed when the memory of the running VMs would exceed
+    /// the memory of the host multiplied by this ratio
+    pub mem_overcommit_ratio: f64,
+    /// Problems which don't prevent the TUI from running (ie. unknown parameters),
+    /// recorded in the activity log on startup
+    pub warnings: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            console_escape: "ctrl-]".to_owned(),
+            console_escape_bytes: vec![0x1d],
+            console_search: "alt-/".to_owned(),
+            console_search_bytes: vec![0x1b, b'/'],
+            console_record: "alt-r".to_owned(),
+            console_record_bytes: vec![0x1b, b'r'],
+            log_max_size: 4 << 20,
+            filters: Vec::new(),
+            sample_interval: Duration::from_secs(2),
+            history_window: Duration::from_secs(15 * 60),
+            mem_overcommit_ratio: 1.0,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Number of samples in the resource usage history of a VM
+    #[must_use]
+    pub fn history_len(&self) -> usize {
+        (self.history_window.as_secs_f64() / self.sample_interval.as_secs_f64()).ceil() as usize
+    }
+
+    pub fn load(base_dir: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+
+        let config_file = format!("{base_dir}{CONFIG_FILE}");
+        let data = match std::fs::read_to_string(&config_file) {
+            Ok(data) => data,
+            // This file is optional
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(config),
+            Err(err) => return Err(format!("Failed to read {config_file}: {err}")),
+        };
+
+        for (key, value) in data
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+        {
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "console_escape" => {
+                    config.console_escape_bytes = console::keys::parse_key(value)
+                        .map(console::keys::key_to_bytes)
+                        .map_err(|err| format!("{config_file}: invalid 'console_escape': {err}"))?;
+                    config.console_escape = value.to_owned();
+                }
+                "console_search" => {
+                    config.console_search_bytes = console::keys::parse_key(value)
+                        .map(console::keys::key_to_bytes)
+                        .map_err(|err| format!("{config_file}: invalid 'console_search': {err}"))?;
+                    config.console_search = value.to_owned();
+                }
+                "console_record" => {
+                    config.console_record_bytes = console::keys::parse_key(value)
+                        .map(console::keys::key_to_bytes)
+                        .map_err(|err| format!("{config_file}: invalid 'console_record': {err}"))?;
+                    config.console_record = value.to_owned();
+                }
+                "log_max_size" => {
+                    config.log_max_size = vm::helpers::parse_size(value)
+                        .map_err(|err| format!("{config_file}: invalid 'log_max_size': {err}"))?;
+                }
+                "sample_interval" => {
+                    config.sample_interval = vm::helpers::parse_duration(value)
+                        .and_then(|interval| {
+                            if interval.is_zero() {
+                                Err("it must be at least 1s".to_owned())
+                            } else {
+                                Ok(interval)
+                            }
+                        })
+                        .map_err(|err| {
+                            format!("{config_file}: invalid 'sample_interval': {err}")
+                        })?;
+                }
+                "history_window" => {
+                    config.history_window = vm::helpers::parse_duration(value)
+                        .map_err(|err| format!("{config_file}: invalid 'history_window': {err}"))?;
+                }
+                "mem_overcommit_ratio" => {
+                    config.mem_overcommit_ratio = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|ratio| *ratio > 0.0)
+                        .ok_or(format!(
+                            "{config_file}: invalid 'mem_overcommit_ratio': it must be a positive number"
+                        ))?;
+                }
+                key if key.starts_with("filter_") => {
+                    let regex = Regex::new(value)
+                        .map_err(|err| format!("{config_file}: invalid '{key}': {err}"))?;
+                    config
+                        .filters
+                        .push((key.trim_start_matches("filter_").to_owned(), regex));
+                }
+                // A parameter of a newer version of the TUI, or a typo
+                key => config
+                    .warnings
+                    .push(format!("{config_file}: unknown parameter '{key}' ignored")),
+            }
+        }
+
+        Ok(config)
+    }
+}
@@ -1,6 +1,10 @@
+pub mod activity;
 mod args;
+mod config;
 mod state;
 
+pub use activity::ActivityLog;
+pub use config::Config;
 pub use state::State;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
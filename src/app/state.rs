@@ -1,25 +1,73 @@
-use crate::app::args;
+use crate::app::{ActivityLog, Config, activity::Severity, args};
+use crate::console::{
+    self, Console,
+    log::{LogView, SerialLogger},
+    player::Player,
+    script::ScriptRun,
+};
 use crate::events::AppEvent;
 use crate::ui::{LOGO, Screen};
-use crate::vm::{self, Vm, VmState};
+use crate::vm::image::ImageUsage;
+use crate::vm::proc::HostMem;
+use crate::vm::qga::Qga;
+use crate::vm::qmp::{ATTACHED_DISK_ID_PREFIX, PortForward, Qmp, QmpError};
+use crate::vm::{self, AttachedDisk, DEFAULT_MEM, OutputLine, SAVED_STATES_DIR, Vm, VmState};
 use ratatui::widgets::TableState;
 use ratatui_image::picker::Picker;
 use ratatui_image::protocol::StatefulProtocol;
+use std::collections::{HashMap, HashSet};
 use std::fs::DirEntry;
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::mpsc::Sender;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// First port tried when looking for a free QMP port
+const QMP_PORTS_START: u16 = 4444;
+
+/// Interval between two refreshes of all the images, in case a change wasn't notified
+const IMAGES_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct State {
     pub base_dir: String,
     pub vms: Vec<Vm>,
     pub kernels: Option<Vec<DirEntry>>,
     pub images: Option<Vec<DirEntry>>,
+    /// Disk usage of the images in `images/` (by filename), updated by `refresh_images()`
+    pub image_usages: HashMap<String, ImageUsage>,
+    /// Images modified since the last tick, refreshed by `refresh_modified_images()`
+    pub modified_images: HashSet<String>,
+    /// Last time all the images were refreshed
+    images_refreshed: Instant,
     pub table_state: TableState,
     pub current_screen: Screen,
     pub exit: bool,
     pub fatal_error: Option<String>,
     pub tx: Sender<AppEvent>,
     pub logo: StatefulProtocol,
+    /// QMP port issues of each VM (by VM name), updated by `check_qmp_ports()`
+    pub qmp_port_issues: HashMap<String, Vec<String>>,
+    /// Settings read from `tui.conf`
+    pub config: Config,
+    /// Serial console opened in `Screen::Console`
+    pub console: Option<Console>,
+    /// Raw console (VM name, serial console path and log to append its output to, cf.
+    /// `take_over_console()`) requested by the user, the terminal is handed over to it by the main loop
+    pub raw_console: Option<(String, PathBuf, Option<PathBuf>)>,
+    /// Loggers of the serial consoles QEMU doesn't log (by VM name), cf. `update_serial_loggers()`
+    serial_loggers: HashMap<String, SerialLogger>,
+    /// Console log displayed in `Screen::Log`
+    pub log_view: Option<LogView>,
+    /// Significant events, displayed in `Screen::Activity`
+    pub activity: ActivityLog,
+    /// Console recording played in `Screen::Player`
+    pub player: Option<Player>,
+    /// Last console script run of each VM (by VM name)
+    pub scripts: HashMap<String, ScriptRun>,
+    /// Memory of the host, updated by `sample_vms()`
+    pub host_mem: Option<HostMem>,
 }
 
 impl State {
@@ -34,14 +82,23 @@ impl State {
         // Sort VMs by name
         vms.sort_by(|vm1, vm2| vm1.name.cmp(&vm2.name));
 
+        let config = Config::load(&base_dir)?;
+        let mut activity = ActivityLog::open(&base_dir);
+        for warning in &config.warnings {
+            activity.record(Severity::Warning, None, warning);
+        }
+
         let picker = Picker::from_query_stdio()?;
         // TODO: picker.protocol_type() to know if the terminal supports images
         let dyn_logo = image::load_from_memory(LOGO)?;
         let logo = picker.new_resize_protocol(dyn_logo);
 
-        Ok(Self {
+        let mut state = Self {
             kernels: vm::helpers::files_in_directory(&format!("{}/kernels", &base_dir)).ok(),
-            images: vm::helpers::files_in_directory(&format!("{}/images", &base_dir)).ok(),
+            images: None,
+            image_usages: HashMap::new(),
+            modified_images: HashSet::new(),
+            images_refreshed: Instant::now(),
             table_state: if vms.is_empty() {
                 TableState::default()
             } else {
@@ -54,7 +111,21 @@ impl State {
             base_dir,
             vms,
             logo,
-        })
+            qmp_port_issues: HashMap::new(),
+            config,
+            console: None,
+            raw_console: None,
+            serial_loggers: HashMap::new(),
+            log_view: None,
+            activity,
+            player: None,
+            scripts: HashMap::new(),
+            host_mem: vm::proc::host_mem(),
+        };
+        state.check_qmp_ports();
+        state.refresh_images();
+
+        Ok(state)
     }
 
     #[must_use]
@@ -62,6 +133,861 @@ impl State {
         self.vms.iter_mut().find(|item| item.name.as_str() == name)
     }
 
+    #[must_use]
+    pub fn selected_vm(&self) -> Option<&Vm> {
+        self.table_state
+            .selected()
+            .and_then(|selected_vm_idx| self.vms.get(selected_vm_idx))
+    }
+
+    /// Returns the screen to display when a QMP based action is requested on the selected VM:
+    /// `screen` if QMP can be used, an explanation otherwise
+    pub fn qmp_screen_for_selected_vm(&self, screen: Screen) -> Screen {
+        match self.selected_vm() {
+            Some(vm) => match vm.qmp_unavailable_reason() {
+                Some(reason) => Screen::QmpUnavailable {
+                    vm_name: vm.name.clone(),
+                    reason,
+                },
+                None => screen,
+            },
+            None => Screen::List,
+        }
+    }
+
+    /// Cores and memory (in bytes) committed by the VMs which are starting or running,
+    /// with the defaults of startnb.sh for the VMs without `cores` or `mem`
+    #[must_use]
+    pub fn committed_resources(&self) -> (u32, u64) {
+        self.vms
+            .iter()
+            .filter(|vm| vm.is_running() || matches!(vm.state, VmState::Starting))
+            .fold((0, 0), |(cores, mem), vm| {
+                (
+                    cores + u32::from(vm.cores.unwrap_or(1)),
+                    mem + vm.mem.unwrap_or(DEFAULT_MEM),
+                )
+            })
+    }
+
+    /// Returns the screen to display when the selected VM is started or stopped: a confirmation if
+    /// starting it would commit more memory than `mem_overcommit_ratio` times the memory of the host,
+    /// `Screen::List` otherwise
+    pub fn start_stop_screen_for_selected_vm(&self) -> Screen {
+        if let Some(vm) = self.selected_vm()
+            && matches!(vm.state, VmState::Stopped)
+            && let Some(host_mem) = self.host_mem
+        {
+            let committed = self.committed_resources().1 + vm.mem.unwrap_or(DEFAULT_MEM);
+            if committed as f64 > host_mem.total as f64 * self.config.mem_overcommit_ratio {
+                return Screen::OvercommitConfirmation {
+                    vm_name: vm.name.clone(),
+                    committed,
+                    total: host_mem.total,
+                    ok: false,
+                };
+            }
+        }
+        Screen::List
+    }
+
+    /// Selects the VM `vm_name` and starts it if it is stopped, ie. once a confirmation has been given
+    /// for it while the VMs may have been re-sorted
+    pub fn start_vm(&mut self, vm_name: &str) {
+        if let Some(idx) = self.vms.iter().position(|vm| vm.name == vm_name)
+            && matches!(self.vms[idx].state, VmState::Stopped)
+        {
+            self.table_state.select(Some(idx));
+            self.start_stop_selected_vm();
+        }
+    }
+
+    /// Connects to the QMP server of the selected VM and calls `f` with the QMP client.
+    /// An `AppEvent::QmpFailed` is sent if something goes wrong.
+    ///
+    /// ⚠️ this function is called from the events handling loop, so `f` is called from a new thread
+    pub fn run_qmp_on_selected_vm<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Qmp) -> Result<(), QmpError> + Send + 'static,
+    {
+        if let Some(selected_vm) = self.selected_vm()
+            && let Some(qmp_addr) = selected_vm.qmp_addr(&self.base_dir)
+        {
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+
+            std::thread::spawn(move || {
+                if let Err(err) = Qmp::connect(&qmp_addr).and_then(|mut qmp| f(&mut qmp)) {
+                    tx.send(AppEvent::QmpFailed {
+                        vm_name,
+                        error: err.to_string(),
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    }
+
+    /// Sends key combinations (QEMU qcodes), one after the other, to the selected VM
+    pub fn send_keys_to_selected_vm(&self, combinations: Vec<Vec<String>>, hold_time: u32) {
+        self.run_qmp_on_selected_vm(move |qmp| {
+            combinations
+                .iter()
+                .try_for_each(|keys| qmp.send_keys(keys, hold_time))
+        });
+    }
+
+    /// Queries the size of the memory balloon of the selected VM.
+    /// The result is sent as an `AppEvent::BalloonQueried`
+    pub fn query_balloon_of_selected_vm(&self) {
+        if let Some(selected_vm) = self.selected_vm() {
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            self.run_qmp_on_selected_vm(move |qmp| {
+                let actual = qmp.query_balloon()?;
+                tx.send(AppEvent::BalloonQueried { vm_name, actual })
+                    .unwrap();
+                Ok(())
+            });
+        }
+    }
+
+    /// Resizes the memory balloon of the selected VM to `target` bytes,
+    /// which can't be more than the configured memory
+    pub fn set_balloon_of_selected_vm(&self, target: u64) -> Result<(), String> {
+        if let Some(selected_vm) = self.selected_vm() {
+            if target == 0 {
+                return Err("The balloon target must be greater than 0".to_owned());
+            }
+            if let Some(mem) = selected_vm.mem
+                && target > mem
+            {
+                return Err(format!(
+                    "The balloon target ({}) can't be more than the configured memory ({})",
+                    vm::helpers::format_bytes(target),
+                    vm::helpers::format_bytes(mem)
+                ));
+            }
+
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            self.run_qmp_on_selected_vm(move |qmp| {
+                qmp.set_balloon(target)?;
+                let actual = qmp.query_balloon()?;
+                tx.send(AppEvent::BalloonQueried { vm_name, actual })
+                    .unwrap();
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Filenames of the images in `images/`, sorted by name
+    #[must_use]
+    pub fn image_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .images
+            .iter()
+            .flatten()
+            .map(|image| image.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Reads the list of images and the disk usage of the images and of the VMs disks again,
+    /// as the allocated size of an image grows while a VM writes to it
+    pub fn refresh_images(&mut self) {
+        self.images_refreshed = Instant::now();
+        self.modified_images.clear();
+        self.images = vm::helpers::files_in_directory(&format!("{}/images", &self.base_dir)).ok();
+        self.image_usages = self
+            .images
+            .iter()
+            .flatten()
+            .filter_map(|image| {
+                let usage = ImageUsage::read(&image.path()).ok()?;
+                Some((image.file_name().to_string_lossy().into_owned(), usage))
+            })
+            .collect();
+        for vm in &mut self.vms {
+            vm.img_usage = vm
+                .img_path(&self.base_dir)
+                .and_then(|path| ImageUsage::read(&path).ok());
+        }
+    }
+
+    /// Refreshes the usage of the images modified since the last call, as the VMs writing to their
+    /// images send many modification events. All the images are refreshed every
+    /// `IMAGES_REFRESH_INTERVAL`
+    pub fn refresh_modified_images(&mut self) {
+        if self.images_refreshed.elapsed() >= IMAGES_REFRESH_INTERVAL {
+            self.refresh_images();
+            return;
+        }
+
+        for image in std::mem::take(&mut self.modified_images) {
+            let path = Path::new(&self.base_dir).join("images").join(&image);
+            let usage = ImageUsage::read(&path).ok();
+            if let Some(image_usage) = self.image_usages.get_mut(&image)
+                && let Some(usage) = usage
+            {
+                *image_usage = usage;
+            }
+            for vm in &mut self.vms {
+                if vm.img_path(&self.base_dir).as_deref() == Some(path.as_path()) {
+                    vm.img_usage = usage;
+                }
+            }
+        }
+    }
+
+    /// Total apparent and allocated sizes of the images in `images/`
+    #[must_use]
+    pub fn images_total(&self) -> (u64, u64) {
+        self.image_usages
+            .values()
+            .fold((0, 0), |(apparent, allocated), usage| {
+                (apparent + usage.apparent, allocated + usage.allocated)
+            })
+    }
+
+    /// Hot-attaches `image` (a filename in `images/`) to the selected VM.
+    /// An image already used by a running VM can't be attached.
+    pub fn attach_image_to_selected_vm(&self, image: &str) -> Result<(), String> {
+        if let Some(selected_vm) = self.selected_vm() {
+            if let Some(vm) = self
+                .vms
+                .iter()
+                .find(|vm| (vm.is_running() || vm.name == selected_vm.name) && vm.uses_image(image))
+            {
+                return Err(format!(
+                    "Image '{image}' is already used by VM '{}'",
+                    vm.name
+                ));
+            }
+
+            // Find the first unused id
+            let id = (0..)
+                .map(|idx| format!("{ATTACHED_DISK_ID_PREFIX}{idx}"))
+                .find(|id| !selected_vm.attached_disks.iter().any(|disk| &disk.id == id))
+                .unwrap();
+            let path = format!("{}images/{image}", self.base_dir);
+            let disk = AttachedDisk {
+                id,
+                image: image.to_owned(),
+            };
+
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            self.run_qmp_on_selected_vm(move |qmp| {
+                qmp.attach_disk(&disk.id, &path)?;
+                tx.send(AppEvent::DiskAttached { vm_name, disk }).unwrap();
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Detaches the disk `id` from the selected VM
+    pub fn detach_disk_from_selected_vm(&self, id: String) {
+        if let Some(selected_vm) = self.selected_vm() {
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            self.run_qmp_on_selected_vm(move |qmp| {
+                qmp.detach_disk(&id)?;
+                tx.send(AppEvent::DiskDetached { vm_name, id }).unwrap();
+                Ok(())
+            });
+        }
+    }
+
+    /// Lists the port forwards of the selected VM.
+    /// The result is sent as an `AppEvent::PortForwardsListed`
+    pub fn list_port_forwards_of_selected_vm(&self) {
+        if let Some(selected_vm) = self.selected_vm() {
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            self.run_qmp_on_selected_vm(move |qmp| {
+                let (netdev, forwards) = qmp.list_port_forwards()?;
+                tx.send(AppEvent::PortForwardsListed {
+                    vm_name,
+                    netdev,
+                    forwards,
+                })
+                .unwrap();
+                Ok(())
+            });
+        }
+    }
+
+    /// Adds the port forward `rule` (ie. "tcp::8080-:80") to the selected VM, then lists them again
+    pub fn add_port_forward_to_selected_vm(&self, netdev: Option<String>, rule: String) {
+        if let Some(selected_vm) = self.selected_vm() {
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            self.run_qmp_on_selected_vm(move |qmp| {
+                qmp.add_port_forward(netdev.as_deref(), &rule)?;
+                let (netdev, forwards) = qmp.list_port_forwards()?;
+                tx.send(AppEvent::PortForwardsListed {
+                    vm_name,
+                    netdev,
+                    forwards,
+                })
+                .unwrap();
+                Ok(())
+            });
+        }
+    }
+
+    /// Removes `forward` from the selected VM, then lists the port forwards again
+    pub fn remove_port_forward_from_selected_vm(
+        &self,
+        netdev: Option<String>,
+        forward: PortForward,
+    ) {
+        if let Some(selected_vm) = self.selected_vm() {
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            self.run_qmp_on_selected_vm(move |qmp| {
+                qmp.remove_port_forward(netdev.as_deref(), &forward)?;
+                let (netdev, forwards) = qmp.list_port_forwards()?;
+                tx.send(AppEvent::PortForwardsListed {
+                    vm_name,
+                    netdev,
+                    forwards,
+                })
+                .unwrap();
+                Ok(())
+            });
+        }
+    }
+
+    /// Saves `forwards` in the `hostfwd` parameter of the selected VM, so they are used on the next start
+    pub fn save_port_forwards_of_selected_vm(
+        &self,
+        forwards: &[PortForward],
+    ) -> Result<(), String> {
+        match self.selected_vm() {
+            Some(vm) if vm.editprotect => Err(format!(
+                "The configuration of VM '{}' is protected (editprotect)",
+                vm.name
+            )),
+            Some(vm) => vm::helpers::set_conf_value(
+                &format!("{}etc/{}.conf", self.base_dir, vm.name),
+                "hostfwd",
+                &forwards
+                    .iter()
+                    .map(|forward| forward.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the screen to display when a guest agent based action is requested on the selected VM:
+    /// `screen` if the guest agent can be used, an explanation otherwise
+    pub fn agent_screen_for_selected_vm(&self, screen: Screen) -> Screen {
+        match self.selected_vm() {
+            Some(vm) => match vm.agent_unavailable_reason() {
+                Some(reason) => Screen::AgentUnavailable {
+                    vm_name: vm.name.clone(),
+                    reason,
+                },
+                None => screen,
+            },
+            None => Screen::List,
+        }
+    }
+
+    /// Asks the guest agent of the selected VM to shut the guest down
+    pub fn shutdown_selected_vm_from_guest(&mut self) {
+        if let Some(selected_vm) = self.selected_vm()
+            && let Some(qga_path) = selected_vm.qga_path(&self.base_dir)
+        {
+            let tx = self.tx.clone();
+            let vm_name = selected_vm.name.clone();
+            if let Some(vm) = self.get_mut_vm_by_name(&vm_name) {
+                vm.expected_exit = Some("guest shutdown".to_owned());
+            }
+            std::thread::spawn(move || {
+                if let Err(err) = Qga::connect(&qga_path).and_then(|mut qga| qga.shutdown()) {
+                    tx.send(AppEvent::QmpFailed {
+                        vm_name,
+                        error: format!("Guest agent: {err}"),
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    }
+
+    /// Opens the serial console of the selected VM and returns `Screen::Console`,
+    /// or the reason why it can't be opened
+    pub fn open_console_of_selected_vm(&mut self) -> Screen {
+        let Some(selected_vm) = self.selected_vm() else {
+            return Screen::List;
+        };
+        let vm_name = selected_vm.name.clone();
+
+        let console = match self.console_unavailable_reason(selected_vm) {
+            Some(reason) => Err(reason),
+            None => selected_vm
+                .serial_path(&self.base_dir)
+                .ok_or_else(|| "No serial console".to_owned()),
+        }
+        .and_then(|path| {
+            let log = self.take_over_console(&vm_name);
+            Console::open(&vm_name, &path, log, self.tx.clone())
+        });
+
+        match console {
+            Ok(console) => {
+                self.console = Some(console);
+                Screen::Console
+            }
+            Err(reason) => Screen::ConsoleUnavailable { vm_name, reason },
+        }
+    }
+
+    /// Returns the reason why the serial console of `vm` can't be used, if any.
+    /// QEMU only serves one client on the console, which is taken by a running script
+    fn console_unavailable_reason(&self, vm: &Vm) -> Option<String> {
+        vm.console_unavailable_reason().or_else(|| {
+            self.scripts
+                .get(&vm.name)
+                .filter(|run| run.result.is_none())
+                .map(|run| format!("Script '{}' is running on the console", run.name))
+        })
+    }
+
+    /// Stops logging the serial console of `vm_name` so that it can be used (cf. `SerialLogger`).
+    /// Returns the log its user must append the output to, when QEMU doesn't log it
+    fn take_over_console(&mut self, vm_name: &str) -> Option<PathBuf> {
+        self.serial_loggers.remove(vm_name);
+        self.vms
+            .iter()
+            .find(|vm| vm.name == vm_name && vm.console_logged_by_qemu == Some(false))
+            .map(|_| console::log::log_path(&self.base_dir, vm_name))
+    }
+
+    /// Logs the serial consoles of the running VMs QEMU doesn't log (ie. started by startnb.sh
+    /// directly), unless they are used by the console or a script, which log them themselves
+    pub fn update_serial_loggers(&mut self) {
+        self.serial_loggers.retain(|vm_name, logger| {
+            !logger.is_finished()
+                && self
+                    .vms
+                    .iter()
+                    .any(|vm| vm.name == *vm_name && vm.is_running())
+        });
+
+        for vm in self.vms.iter_mut().filter(|vm| vm.is_running()) {
+            if vm.console_logged_by_qemu.is_none() {
+                vm.console_logged_by_qemu = vm.qemu_logs_console(&self.base_dir);
+                if vm.console_logged_by_qemu == Some(false)
+                    && vm.serial_socket.is_some()
+                    && let Err(err) = console::log::write_logging_marker(&self.base_dir, &vm.name)
+                {
+                    self.activity.warning(&vm.name, &err);
+                }
+            }
+
+            let in_use = self
+                .console
+                .as_ref()
+                .is_some_and(|console| console.vm_name == vm.name)
+                || self
+                    .scripts
+                    .get(&vm.name)
+                    .is_some_and(|run| run.result.is_none());
+            if vm.console_logged_by_qemu == Some(false)
+                && !in_use
+                && !self.serial_loggers.contains_key(&vm.name)
+                && let Some(path) = vm.serial_path(&self.base_dir)
+                && let Ok(logger) = SerialLogger::start(&self.base_dir, &vm.name, &path)
+            {
+                self.serial_loggers.insert(vm.name.clone(), logger);
+            }
+        }
+    }
+
+    /// Runs the script `name` (in `scripts/<vm_name>/`) on the console of `vm_name`, its first
+    /// `expect` matching `output` too (cf. `ScriptRun::start()`)
+    pub fn run_script(&mut self, vm_name: &str, name: &str, output: String) -> Result<(), String> {
+        let vm = self
+            .vms
+            .iter()
+            .find(|vm| vm.name == vm_name)
+            .ok_or(format!("Unknown VM '{vm_name}'"))?;
+        if let Some(reason) = self.console_unavailable_reason(vm) {
+            return Err(reason);
+        }
+        if self
+            .console
+            .as_ref()
+            .is_some_and(|console| console.vm_name == vm_name)
+        {
+            return Err(format!("The console of '{vm_name}' is opened"));
+        }
+        let serial_path = vm
+            .serial_path(&self.base_dir)
+            .ok_or("No serial console".to_owned())?;
+
+        let log = self.take_over_console(vm_name);
+        let run = ScriptRun::start(
+            &self.base_dir,
+            vm_name,
+            name,
+            &serial_path,
+            output,
+            log,
+            self.tx.clone(),
+        )?;
+        self.activity
+            .info(vm_name, &format!("Running script '{name}'"));
+        self.scripts.insert(vm_name.to_owned(), run);
+        Ok(())
+    }
+
+    /// Requests the terminal to be handed over to the serial console of the selected VM (cf. `raw_console`).
+    /// Returns the reason why it can't be done, if any
+    pub fn request_raw_console_of_selected_vm(&mut self) -> Screen {
+        let Some(selected_vm) = self.selected_vm() else {
+            return Screen::List;
+        };
+
+        match self.console_unavailable_reason(selected_vm) {
+            Some(reason) => Screen::ConsoleUnavailable {
+                vm_name: selected_vm.name.clone(),
+                reason,
+            },
+            None => {
+                let vm_name = selected_vm.name.clone();
+                if let Some(path) = selected_vm.serial_path(&self.base_dir) {
+                    let log = self.take_over_console(&vm_name);
+                    self.raw_console = Some((vm_name, path, log));
+                }
+                Screen::List
+            }
+        }
+    }
+
+    /// Opens the console log of the selected VM in `log_view`
+    pub fn open_log_of_selected_vm(&mut self) -> Screen {
+        match self.selected_vm() {
+            Some(selected_vm) => {
+                self.log_view = Some(LogView::open(
+                    &self.base_dir,
+                    &selected_vm.name,
+                    selected_vm.console_log_notice(&self.base_dir),
+                ));
+                Screen::Log
+            }
+            None => Screen::List,
+        }
+    }
+
+    /// Rotates the console logs and the activity log which are bigger than `config.log_max_size`
+    pub fn rotate_logs(&mut self) {
+        for vm in self.vms.iter().filter(|vm| vm.is_running()) {
+            let path = console::log::log_path(&self.base_dir, &vm.name);
+            if let Err(err) = console::log::rotate_if_needed(&path, self.config.log_max_size)
+                && let Some(log_view) = self.log_view.as_mut()
+                && log_view.vm_name == vm.name
+            {
+                log_view.error = Some(err);
+            }
+        }
+        if let Err(err) =
+            console::log::rotate_if_needed(&self.activity.path, self.config.log_max_size)
+        {
+            self.activity.write_error = Some(err);
+        }
+    }
+
+    /// Hibernates the selected VM: its state is saved in `states/` and QEMU is stopped.
+    /// The VM will be resumed from this state on its next start.
+    pub fn hibernate_selected_vm(&mut self) -> Result<(), String> {
+        if let Some(selected_vm) = self.selected_vm() {
+            let vm_name = selected_vm.name.clone();
+            let states_dir = format!("{}{SAVED_STATES_DIR}", self.base_dir);
+            std::fs::create_dir_all(&states_dir)
+                .map_err(|err| format!("Failed to create {states_dir}: {err}"))?;
+
+            let path = selected_vm
+                .saved_state_path(&self.base_dir)
+                .to_string_lossy()
+                .into_owned();
+            self.run_qmp_on_selected_vm(move |qmp| qmp.save_state_and_quit(&path));
+            self.activity.info(&vm_name, "Hibernating");
+            if let Some(vm) = self.get_mut_vm_by_name(&vm_name) {
+                vm.expected_exit = Some("hibernation".to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths of the saved states of the hibernated VMs, sorted by name
+    #[must_use]
+    pub fn saved_states(&self) -> Vec<PathBuf> {
+        let mut saved_states: Vec<PathBuf> =
+            vm::helpers::files_in_directory(&format!("{}{SAVED_STATES_DIR}", self.base_dir))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|file| file.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "state")
+                })
+                .collect();
+        saved_states.sort();
+        saved_states
+    }
+
+    /// Plays the console recording `path`
+    pub fn play_recording(&mut self, path: &Path) -> Screen {
+        match Player::open(path, self.tx.clone()) {
+            Ok(player) => {
+                self.player = Some(player);
+                Screen::Player
+            }
+            Err(error) => Screen::RecordingFailed { error },
+        }
+    }
+
+    /// Deletes a saved state, the VM will do a cold boot on its next start
+    pub fn delete_saved_state(&mut self, path: &Path) -> Result<(), String> {
+        std::fs::remove_file(path)
+            .map_err(|err| format!("Failed to delete {}: {err}", path.display()))?;
+        let base_dir = self.base_dir.clone();
+        if let Some(vm_name) = path.file_stem().and_then(|vm_name| vm_name.to_str())
+            && let Some(vm) = self.get_mut_vm_by_name(vm_name)
+        {
+            vm.update_state(&base_dir);
+        }
+        Ok(())
+    }
+
+    /// Looks for duplicated QMP ports and for QMP ports already used on the host.
+    /// Probing a port binds it, so this is only done when a configuration changes and when the
+    /// QMP issues are shown, not while sampling the VMs
+    pub fn check_qmp_ports(&mut self) {
+        self.qmp_port_issues = self
+            .vms
+            .iter()
+            .map(|vm| {
+                let mut issues = Vec::new();
+                if let Some(qmp_port) = vm.qmp_port {
+                    for other_vm in self.vms.iter().filter(|other_vm| {
+                        other_vm.name != vm.name && other_vm.qmp_port == vm.qmp_port
+                    }) {
+                        issues.push(format!(
+                            "QMP port {qmp_port} is also used by VM '{}'",
+                            other_vm.name
+                        ));
+                    }
+                    // Unless the VM is stopped, the port is used (or about to be) by its own QEMU
+                    // process, which binding it could prevent from starting
+                    if matches!(vm.state, VmState::Stopped) && !vm::helpers::is_port_free(qmp_port)
+                    {
+                        issues.push(format!(
+                            "QMP port {qmp_port} is already in use on this host"
+                        ));
+                    }
+                }
+                if let Some(qmp_socket) = &vm.qmp_socket {
+                    for other_vm in self.vms.iter().filter(|other_vm| {
+                        other_vm.name != vm.name && other_vm.qmp_socket == vm.qmp_socket
+                    }) {
+                        issues.push(format!(
+                            "QMP socket {qmp_socket} is also used by VM '{}'",
+                            other_vm.name
+                        ));
+                    }
+                    // QEMU creates the socket, but not its directory
+                    if let Some(dir) = Path::new(&self.base_dir).join(qmp_socket).parent()
+                        && !dir.is_dir()
+                    {
+                        issues.push(format!(
+                            "The directory of QMP socket {qmp_socket} doesn't exist"
+                        ));
+                    }
+                }
+                (vm.name.clone(), issues)
+            })
+            .filter(|(_, issues)| !issues.is_empty())
+            .collect();
+    }
+
+    /// Returns a port that isn't used by any VM nor by another process on the host
+    #[must_use]
+    pub fn find_free_qmp_port(&self) -> Option<u16> {
+        (QMP_PORTS_START..=u16::MAX).find(|port| {
+            !self.vms.iter().any(|vm| vm.qmp_port == Some(*port))
+                && vm::helpers::is_port_free(*port)
+        })
+    }
+
+    /// Returns the screen listing the QMP port issues of the selected VM
+    pub fn qmp_port_issues_screen(&mut self) -> Screen {
+        self.check_qmp_ports();
+        match self.selected_vm() {
+            Some(vm) => {
+                let mut issues = self
+                    .qmp_port_issues
+                    .get(&vm.name)
+                    .cloned()
+                    .unwrap_or_default();
+                if let VmState::InvalidConfiguration { cause } = &vm.state
+                    && cause.contains("'qmp_port'")
+                {
+                    issues.push(cause.clone());
+                }
+                let free_port = if issues.is_empty() || vm.is_running() {
+                    None
+                } else {
+                    self.find_free_qmp_port()
+                };
+                Screen::QmpPortIssues {
+                    issues,
+                    free_port,
+                    ok: false,
+                }
+            }
+            None => Screen::List,
+        }
+    }
+
+    /// Writes `qmp_port` in the configuration file of the selected VM
+    pub fn set_qmp_port_of_selected_vm(&self, qmp_port: u16) -> Result<(), String> {
+        match self.selected_vm() {
+            Some(vm) if vm.editprotect => Err(format!(
+                "The configuration of VM '{}' is protected (editprotect)",
+                vm.name
+            )),
+            Some(vm) => vm::helpers::set_conf_value(
+                &format!("{}etc/{}.conf", self.base_dir, vm.name),
+                "qmp_port",
+                &qmp_port.to_string(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Samples the statistics of the running VMs, called every `config.sample_interval`
+    ///
+    /// ⚠️ this function is called from the events handling loop, QMP commands are sent from new threads
+    pub fn sample_vms(&mut self) {
+        self.host_mem = vm::proc::host_mem();
+
+        let mut boot_scripts = Vec::new();
+        for vm in self.vms.iter_mut().filter(|vm| vm.is_running()) {
+            if vm.check_ready(&self.base_dir) {
+                let boot_duration = vm.boot_duration.unwrap_or_default();
+                self.activity.info(
+                    &vm.name,
+                    &format!("Ready after {:.1}s", boot_duration.as_secs_f64()),
+                );
+                if let Some(boot_script) = &vm.boot_script {
+                    boot_scripts.push((vm.name.clone(), boot_script.clone()));
+                }
+            }
+
+            if let Some(qga_path) = vm.qga_path(&self.base_dir)
+                && !vm.agent_pending
+            {
+                vm.agent_pending = true;
+                let tx = self.tx.clone();
+                let vm_name = vm.name.clone();
+                std::thread::spawn(move || {
+                    let info = Qga::connect(&qga_path)
+                        .and_then(|mut qga| {
+                            qga.ping()?;
+                            Ok(qga.guest_info())
+                        })
+                        .map_err(|err| err.to_string());
+                    tx.send(AppEvent::AgentQueried { vm_name, info }).unwrap();
+                });
+            }
+
+            if let Some(qmp_addr) = vm.qmp_addr(&self.base_dir)
+                && !vm.attached_disks_queried
+            {
+                vm.attached_disks_queried = true;
+                let tx = self.tx.clone();
+                let vm_name = vm.name.clone();
+                std::thread::spawn(move || {
+                    let disks = Qmp::connect(&qmp_addr)
+                        .and_then(|mut qmp| qmp.query_attached_disks())
+                        .map_err(|err| err.to_string());
+                    tx.send(AppEvent::AttachedDisksQueried { vm_name, disks })
+                        .unwrap();
+                });
+            }
+
+            // The vCPUs threads don't change while the VM is running, we only query them once
+            if let Some(qmp_addr) = vm.qmp_addr(&self.base_dir)
+                && vm.vcpu_threads.is_empty()
+                && !vm.vcpu_threads_pending
+            {
+                vm.vcpu_threads_pending = true;
+                let tx = self.tx.clone();
+                let vm_name = vm.name.clone();
+                std::thread::spawn(move || {
+                    let threads = Qmp::connect(&qmp_addr)
+                        .and_then(|mut qmp| qmp.query_vcpu_threads())
+                        .map_err(|err| err.to_string());
+                    tx.send(AppEvent::VcpuThreadsQueried { vm_name, threads })
+                        .unwrap();
+                });
+            }
+            vm.sample_cpu(Instant::now());
+            vm.sample_rss();
+            vm.record_history(self.config.history_len());
+            vm.sample_vcpus(Instant::now());
+            vm.sample_net(Instant::now());
+
+            if let Some(qmp_addr) = vm.qmp_addr(&self.base_dir)
+                && !vm.blockstats_pending
+            {
+                vm.blockstats_pending = true;
+                let tx = self.tx.clone();
+                let vm_name = vm.name.clone();
+                std::thread::spawn(move || {
+                    let stats = Qmp::connect(&qmp_addr)
+                        .and_then(|mut qmp| qmp.query_blockstats())
+                        .map_err(|err| err.to_string());
+                    tx.send(AppEvent::BlockStatsSampled {
+                        vm_name,
+                        time: Instant::now(),
+                        stats,
+                    })
+                    .unwrap();
+                });
+            }
+        }
+
+        // The boot scripts are run once the VMs are ready
+        for (vm_name, boot_script) in boot_scripts {
+            // The VM is ready once the ready pattern (ie. "login:") has been written,
+            // so the script is given the console output since the start
+            let output = console::log::lines_since_last_start(&console::log::log_path(
+                &self.base_dir,
+                &vm_name,
+            ))
+            .map(|lines| lines.join("\n"))
+            .unwrap_or_default();
+            if let Err(err) = self.run_script(&vm_name, &boot_script, output) {
+                self.activity.error(
+                    &vm_name,
+                    &format!("Failed to run boot script '{boot_script}': {err}"),
+                );
+            }
+        }
+    }
+
     /// This function starts or stops the currently selected VM depending on its state
     ///
     /// ⚠️ this function is called from the events handling loop, so it **must** be quick! (that's why it starts a thread when necessary)
@@ -76,18 +1002,77 @@ impl State {
                 | VmState::StoppingToDelete => {
                     // We don't do anything in thoses cases
                 }
-                VmState::Running { .. } => {
-                    let _ = selected_vm.kill().map_err(|err| {
-                        self.tx
+                VmState::Booting { pid } | VmState::Running { pid } => {
+                    let pid = *pid;
+                    match selected_vm.kill() {
+                        Ok(()) => self
+                            .activity
+                            .info(&selected_vm.name, &format!("Killing QEMU (PID {pid})")),
+                        Err(err) => self
+                            .tx
                             .send(AppEvent::KillFailed {
                                 vm_name: selected_vm.name.clone(),
                                 error: err,
                             })
-                            .unwrap()
-                    });
+                            .unwrap(),
+                    }
                 }
                 VmState::Stopped => {
                     selected_vm.state = VmState::Starting;
+                    selected_vm.boot_started = Some(Instant::now());
+                    selected_vm.boot_duration = None;
+                    selected_vm.start_log.clear();
+
+                    let mut startnb_args = vec![
+                        "-f".to_owned(),
+                        format!("etc/{}.conf", selected_vm.name),
+                        "-d".to_owned(),
+                    ];
+
+                    // QEMU arguments added by the TUI to the `extra` parameter
+                    let mut extra_args = Vec::new();
+
+                    // A hibernated VM is resumed from its saved state instead of booting
+                    let saved_state = selected_vm.saved_state_path(&self.base_dir);
+                    let resuming_state = selected_vm.resuming_state_path(&self.base_dir);
+                    let resuming = selected_vm.saved_state
+                        && std::fs::rename(&saved_state, &resuming_state).is_ok();
+                    if resuming {
+                        extra_args.push(format!("-incoming file:{}", resuming_state.display()));
+                        self.activity
+                            .info(&selected_vm.name, "Resuming from the saved state");
+                    } else {
+                        self.activity.info(&selected_vm.name, "Starting");
+                    }
+
+                    // The serial console is exported on a Unix socket and logged in `logs/`
+                    selected_vm.ready_log_offset = None;
+                    selected_vm.console_logged_by_qemu = None;
+                    if let Some(serial_args) = selected_vm.serial_chardev_args(&self.base_dir) {
+                        selected_vm.console_logged_by_qemu = Some(true);
+                        // The log is only a convenience, the VM is started even if it can't be written
+                        // (its readiness isn't watched then)
+                        selected_vm.ready_log_offset = console::log::write_start_marker(
+                            &self.base_dir,
+                            &selected_vm.name,
+                            resuming,
+                        )
+                        .ok();
+                        extra_args.insert(0, serial_args);
+                    }
+
+                    if let Some(qmp_args) = selected_vm.qmp_socket_args(&self.base_dir) {
+                        extra_args.push(qmp_args);
+                    }
+
+                    if !extra_args.is_empty() {
+                        startnb_args.push("-x".to_owned());
+                        startnb_args.push(format!(
+                            "{} {}",
+                            selected_vm.extra.as_deref().unwrap_or("").trim_matches('"'),
+                            extra_args.join(" ")
+                        ));
+                    }
 
                     // We have to clone those variables because they will be used by the thread created below
                     let tx = self.tx.clone();
@@ -111,35 +1096,63 @@ impl State {
                                     return;
                                 }
                             };
-                        let startnb_output = std::process::Command::new(startnb_path)
-                            .args(["-f", &format!("etc/{}.conf", selected_vm_name), "-d"])
+                        let child = std::process::Command::new(startnb_path)
+                            .args(&startnb_args)
                             .current_dir(&base_dir)
-                            .output();
+                            .stdout(Stdio::piped())
+                            .stderr(Stdio::piped())
+                            .spawn();
+                        let mut child = match child {
+                            Ok(child) => child,
+                            Err(err) => {
+                                tx.send(AppEvent::StartNbFailed {
+                                    vm_name: selected_vm_name,
+                                    error: format!("startnb.sh failed: {err}"),
+                                    stdout: String::new(),
+                                    stderr: String::new(),
+                                })
+                                .unwrap();
+                                return;
+                            }
+                        };
+
+                        // stdout and stderr are streamed line by line, and collected for the error popup
+                        let stdout_reader = child.stdout.take().map(|stdout| {
+                            stream_output(stdout, false, selected_vm_name.clone(), tx.clone())
+                        });
+                        let stderr_reader = child.stderr.take().map(|stderr| {
+                            stream_output(stderr, true, selected_vm_name.clone(), tx.clone())
+                        });
+                        let status = child.wait();
+                        let collect = |reader: Option<JoinHandle<String>>| {
+                            reader
+                                .and_then(|reader| reader.join().ok())
+                                .unwrap_or_default()
+                        };
+                        let stdout = collect(stdout_reader);
+                        let stderr = collect(stderr_reader);
 
                         // Sending the result through tx
-                        match startnb_output {
-                            Ok(output) => {
-                                if output.status.success() {
-                                    tx.send(AppEvent::StartNbSuccess {
-                                        vm_name: selected_vm_name,
-                                    })
-                                    .unwrap()
-                                } else {
-                                    tx.send(AppEvent::StartNbFailed {
-                                        vm_name: selected_vm_name,
-                                        error: "startnb.sh failed!".to_owned(),
-                                        stdout: String::from_utf8(output.stdout).unwrap(),
-                                        stderr: String::from_utf8(output.stderr).unwrap(),
-                                    })
-                                    .unwrap()
-                                }
-                            }
+                        match status {
+                            Ok(status) if status.success() => tx
+                                .send(AppEvent::StartNbSuccess {
+                                    vm_name: selected_vm_name,
+                                })
+                                .unwrap(),
+                            Ok(_) => tx
+                                .send(AppEvent::StartNbFailed {
+                                    vm_name: selected_vm_name,
+                                    error: "startnb.sh failed!".to_owned(),
+                                    stdout,
+                                    stderr,
+                                })
+                                .unwrap(),
                             Err(err) => tx
                                 .send(AppEvent::StartNbFailed {
                                     vm_name: selected_vm_name,
                                     error: format!("startnb.sh failed: {err}"),
-                                    stdout: String::new(),
-                                    stderr: String::new(),
+                                    stdout,
+                                    stderr,
                                 })
                                 .unwrap(),
                         }
@@ -196,6 +1209,25 @@ impl State {
                     self.vms.sort_by(|vm1, vm2| vm1.name.cmp(&vm2.name));
                 }
             }
+            self.check_qmp_ports();
+        }
+    }
+
+    /// `conf_file` **must** be an absolute path
+    pub fn reload_vm(&mut self, conf_file: &str) {
+        let relative_conf_file = conf_file.strip_prefix(&self.base_dir).unwrap();
+
+        if let Some(vm_name) = relative_conf_file
+            .strip_prefix("etc/")
+            .and_then(|value| value.strip_suffix(".conf"))
+        {
+            let base_dir = self.base_dir.clone();
+            if let Some(vm) = self.get_mut_vm_by_name(vm_name)
+                && let Ok(new_conf) = vm::helpers::vm_from_conf(PathBuf::from(conf_file), &base_dir)
+            {
+                vm.update_conf(new_conf);
+            }
+            self.check_qmp_ports();
         }
     }
 
@@ -229,6 +1261,37 @@ impl State {
                     self.vms.retain(|item| item.name != vm_name);
                 }
             }
+            self.check_qmp_ports();
         }
     }
 }
+
+/// Reads the output of startnb.sh (`stderr` telling which one) line by line in a new thread,
+/// sending each line as an `AppEvent::StartNbOutput`. The thread returns the whole output.
+fn stream_output<R: Read + Send + 'static>(
+    output: R,
+    stderr: bool,
+    vm_name: String,
+    tx: Sender<AppEvent>,
+) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(output);
+        let mut all = String::new();
+        let mut buf = Vec::new();
+        // The output may not be valid UTF-8
+        while reader.read_until(b'\n', &mut buf).is_ok_and(|len| len > 0) {
+            let text = String::from_utf8_lossy(&buf)
+                .trim_end_matches(['\n', '\r'])
+                .to_owned();
+            all.push_str(&text);
+            all.push('\n');
+            tx.send(AppEvent::StartNbOutput {
+                vm_name: vm_name.clone(),
+                line: OutputLine { stderr, text },
+            })
+            .unwrap();
+            buf.clear();
+        }
+        all
+    })
+}
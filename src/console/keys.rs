@@ -0,0 +1,81 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Converts a key event into the bytes a VT100 compatible terminal would send
+pub fn key_to_bytes(key: KeyEvent) -> Vec<u8> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+    let bytes: Vec<u8> = match key.code {
+        KeyCode::Char(c) if ctrl => match c {
+            'a'..='z' | 'A'..='Z' => vec![c.to_ascii_lowercase() as u8 - b'a' + 1],
+            // crossterm reports the 0x1c-0x1f control characters as Ctrl-4 to Ctrl-7
+            '4'..='7' => vec![c as u8 - b'4' + 0x1c],
+            '[' | '\\' | ']' | '^' | '_' => vec![c as u8 ^ 0x40],
+            ' ' | '@' | '2' => vec![0],
+            c => c.to_string().into_bytes(),
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => b"\x1b[A".to_vec(),
+        KeyCode::Down => b"\x1b[B".to_vec(),
+        KeyCode::Right => b"\x1b[C".to_vec(),
+        KeyCode::Left => b"\x1b[D".to_vec(),
+        KeyCode::Home => b"\x1b[H".to_vec(),
+        KeyCode::End => b"\x1b[F".to_vec(),
+        KeyCode::Insert => b"\x1b[2~".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        KeyCode::PageUp => b"\x1b[5~".to_vec(),
+        KeyCode::PageDown => b"\x1b[6~".to_vec(),
+        KeyCode::F(n @ 1..=4) => vec![0x1b, b'O', b'P' + n - 1],
+        KeyCode::F(n @ 5..=12) => {
+            let code = [15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5];
+            format!("\x1b[{code}~").into_bytes()
+        }
+        _ => Vec::new(),
+    };
+
+    // Alt is sent as an Escape prefix
+    if alt && !bytes.is_empty() {
+        [vec![0x1b], bytes].concat()
+    } else {
+        bytes
+    }
+}
+
+/// Parses a key description like "ctrl-]", "alt-x" or "f12"
+pub fn parse_key(input: &str) -> Result<KeyEvent, String> {
+    let input = input.trim().to_lowercase();
+    // The key itself can be '-' (ie. "ctrl--")
+    let (modifiers, key) = match input.strip_suffix("--") {
+        Some(modifiers) => (modifiers, "-"),
+        None => input.rsplit_once('-').unwrap_or(("", &input)),
+    };
+
+    let mut key_modifiers = KeyModifiers::NONE;
+    for modifier in modifiers.split('-').filter(|modifier| !modifier.is_empty()) {
+        key_modifiers |= match modifier {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("unknown modifier '{modifier}' in '{input}'")),
+        };
+    }
+
+    let code = match key {
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "enter" => KeyCode::Enter,
+        "backspace" => KeyCode::Backspace,
+        key if key.chars().count() == 1 => KeyCode::Char(key.chars().next().unwrap()),
+        key => match key.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => return Err(format!("unknown key '{key}' in '{input}'")),
+        },
+    };
+
+    Ok(KeyEvent::new(code, key_modifiers))
+}
@@ -0,0 +1,512 @@
+//! Serial console logs, written by QEMU in `logs/<name>.log` (cf. the `logfile` chardev option)
+//! so that nothing is lost while the TUI isn't running.
+//! The consoles QEMU doesn't log (ie. VMs started by startnb.sh directly) are logged by the TUI
+//! while it runs, cf. `SerialLogger`.
+
+use regex::Regex;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+use crate::console::search::Search;
+use crate::console::{Connection, POLL_TIMEOUT_MS};
+use crate::vm::helpers::format_utc;
+
+/// Directory of the console logs, in the base directory
+pub const LOGS_DIR: &str = "logs";
+
+/// Number of rotated logs kept (`<name>.log.1` being the most recent)
+const LOG_ROTATIONS: usize = 3;
+
+/// Beginning of the lines written in the log by the TUI before a VM is started
+const MARKER: &str = "=== smolBSD-tui:";
+
+/// Beginning of the line written in the log by the TUI before a VM boots
+const BOOT_MARKER: &str = "=== smolBSD-tui: booting";
+
+/// Line printed by the NetBSD kernel at the beginning of each boot
+const NETBSD_BANNER: &str = "The NetBSD Foundation, Inc.";
+
+pub fn log_path(base_dir: &str, vm_name: &str) -> PathBuf {
+    Path::new(base_dir)
+        .join(LOGS_DIR)
+        .join(format!("{vm_name}.log"))
+}
+
+/// Appends a line to the log of `vm_name` before it is started, either to boot or to be resumed
+/// from a saved state. The logs directory is created if needed.
+/// Returns the size of the log after the line, where the output of this start begins.
+pub fn write_start_marker(base_dir: &str, vm_name: &str, resuming: bool) -> Result<u64, String> {
+    let path = log_path(base_dir, vm_name);
+    std::fs::create_dir_all(Path::new(base_dir).join(LOGS_DIR))
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| {
+            write!(
+                file,
+                "\r\n{MARKER} {} {vm_name} ({} UTC) ===\r\n",
+                if resuming { "resuming" } else { "booting" },
+                format_utc(SystemTime::now())
+            )?;
+            file.metadata().map(|metadata| metadata.len())
+        })
+        .map_err(|err| format!("Failed to write to {}: {err}", path.display()))
+}
+
+/// Appends a line to the log of `vm_name` when the TUI starts logging its console, the output
+/// written before being missing
+pub fn write_logging_marker(base_dir: &str, vm_name: &str) -> Result<(), String> {
+    std::fs::create_dir_all(Path::new(base_dir).join(LOGS_DIR))
+        .map_err(|err| format!("Failed to create {LOGS_DIR}: {err}"))?;
+    append(
+        &log_path(base_dir, vm_name),
+        format!(
+            "\r\n{MARKER} logging {vm_name} ({} UTC), the output written before is missing ===\r\n",
+            format_utc(SystemTime::now())
+        )
+        .as_bytes(),
+    )
+}
+
+/// Appends the output of a console to the log `path`
+pub fn append(path: &Path, data: &[u8]) -> Result<(), String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(data))
+        .map_err(|err| format!("Failed to write to {}: {err}", path.display()))
+}
+
+/// Reads what has been appended to the log `path` after `offset`, which is updated.
+/// The log is read from its beginning when it has been rotated (truncated).
+pub fn read_appended(path: &Path, offset: &mut u64) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    File::open(path)
+        .and_then(|mut file| {
+            let len = file.metadata()?.len();
+            if len < *offset {
+                *offset = 0;
+            }
+            file.seek(SeekFrom::Start(*offset))?;
+            file.take(len - *offset).read_to_end(&mut data)
+        })
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    *offset += data.len() as u64;
+    Ok(data)
+}
+
+/// Lines of the log `path` written since the VM was last started or since the TUI logs its console
+/// (all of them if the marker has been rotated away)
+pub fn lines_since_last_start(path: &Path) -> Result<Vec<String>, String> {
+    let data =
+        std::fs::read(path).map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    let lines: Vec<String> = String::from_utf8_lossy(&data)
+        .lines()
+        .map(clean_line)
+        .collect();
+    let start = lines
+        .iter()
+        .rposition(|line| line.starts_with(MARKER))
+        .map_or(0, |idx| idx + 1);
+    Ok(lines[start..].to_vec())
+}
+
+/// Rotates the log `path` if it is bigger than `max_size`.
+/// QEMU keeps the log opened in append mode, so it is copied then truncated instead of being renamed.
+pub fn rotate_if_needed(path: &Path, max_size: u64) -> Result<(), String> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.len() > max_size => {}
+        _ => return Ok(()),
+    }
+
+    let rotated = |idx: usize| PathBuf::from(format!("{}.{idx}", path.display()));
+    for idx in (1..LOG_ROTATIONS).rev() {
+        if rotated(idx).exists() {
+            std::fs::rename(rotated(idx), rotated(idx + 1))
+                .map_err(|err| format!("Failed to rotate {}: {err}", path.display()))?;
+        }
+    }
+    std::fs::copy(path, rotated(1))
+        .and_then(|_| OpenOptions::new().write(true).open(path))
+        .and_then(|file| file.set_len(0))
+        .map_err(|err| format!("Failed to rotate {}: {err}", path.display()))
+}
+
+/// Logger of a serial console QEMU doesn't log, reading it in a dedicated thread.
+/// QEMU serves a single client per chardev socket, so it must be dropped before connecting
+/// to the console, whose output is then logged by its user.
+pub struct SerialLogger {
+    connection: Connection,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SerialLogger {
+    /// Connects to the serial console `serial_path` of VM `vm_name` and appends its output to its log
+    pub fn start(base_dir: &str, vm_name: &str, serial_path: &Path) -> Result<Self, String> {
+        let connection = Connection::open(serial_path)?;
+        let mut reader = connection.try_clone()?;
+        let path = log_path(base_dir, vm_name);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stop_clone = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while !stop_clone.load(Ordering::Relaxed) {
+                let mut pollfd = libc::pollfd {
+                    fd: reader.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                match unsafe { libc::poll(&mut pollfd, 1, POLL_TIMEOUT_MS) } {
+                    0 => continue,
+                    ret if ret < 0 => {
+                        if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+                        {
+                            continue;
+                        }
+                        return;
+                    }
+                    _ => {}
+                }
+                match reader.read(&mut buf) {
+                    Ok(0) => return,
+                    Ok(len) => {
+                        if append(&path, &buf[..len]).is_err() {
+                            return;
+                        }
+                    }
+                    Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(_) => return,
+                }
+            }
+        });
+
+        Ok(SerialLogger {
+            connection,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns true if the console has been closed (ie. the VM stopped) or the log can't be written
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+}
+
+impl Drop for SerialLogger {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Connection::Socket(stream) = &self.connection {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+        // The console is free once the thread has released its connection
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Removes the terminal escape sequences and control characters from a line of the log
+pub fn clean_line(line: &str) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters until a final byte in '@'..='~'
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: until BEL or ST (ESC \)
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\t' => res.push(c),
+            c if c.is_control() => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Log viewer of a VM
+pub struct LogView {
+    pub vm_name: String,
+    pub path: PathBuf,
+    /// Lines of the log, only those matching `filter` when it is set
+    pub lines: Vec<String>,
+    /// Saved filter (name and regex) applied to the log
+    pub filter: Option<(String, Regex)>,
+    pub search: Search,
+    /// First displayed line, `None` when following the end of the log (tail)
+    pub top: Option<usize>,
+    /// Number of lines displayed, updated when the view is rendered
+    pub height: usize,
+    /// Set when the log can't be read
+    pub error: Option<String>,
+    /// Why the log is missing or incomplete (cf. `Vm::console_log_notice()`)
+    pub notice: Option<String>,
+    /// Size of the log already read
+    offset: u64,
+    /// End of the log after its last newline
+    partial: Vec<u8>,
+    /// Set when the last element of `lines` is `partial`, replaced by the next `reload()`
+    partial_shown: bool,
+}
+
+impl LogView {
+    pub fn open(base_dir: &str, vm_name: &str, notice: Option<String>) -> Self {
+        let mut log_view = LogView {
+            vm_name: vm_name.to_owned(),
+            path: log_path(base_dir, vm_name),
+            lines: Vec::new(),
+            filter: None,
+            search: Search::default(),
+            top: None,
+            height: 0,
+            error: None,
+            notice,
+            offset: 0,
+            partial: Vec::new(),
+            partial_shown: false,
+        };
+        log_view.reload();
+        log_view
+    }
+
+    /// Reads what has been appended to the log since the last time, ie. to follow its end
+    pub fn reload(&mut self) {
+        let len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(err) => {
+                self.clear();
+                self.error = Some(format!("Failed to read {}: {err}", self.path.display()));
+                self.search.update(&self.lines);
+                return;
+            }
+        };
+        self.error = None;
+        // The log has been rotated (copied then truncated)
+        if len < self.offset {
+            self.clear();
+        }
+        if len == self.offset {
+            return;
+        }
+
+        let mut data = Vec::new();
+        if let Err(err) = File::open(&self.path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(self.offset))?;
+            file.take(len - self.offset).read_to_end(&mut data)
+        }) {
+            self.error = Some(format!("Failed to read {}: {err}", self.path.display()));
+            return;
+        }
+        self.offset += data.len() as u64;
+
+        if self.partial_shown {
+            self.lines.pop();
+        }
+        self.partial.extend_from_slice(&data);
+        let complete = self
+            .partial
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |idx| idx + 1);
+        let text = String::from_utf8_lossy(&self.partial[..complete]).into_owned();
+        self.partial.drain(..complete);
+        for line in text.lines() {
+            self.push_line(line);
+        }
+        // The last line (ie. a login prompt) is shown before its newline is written
+        let partial = String::from_utf8_lossy(&self.partial).into_owned();
+        self.partial_shown = !partial.is_empty() && self.push_line(&partial);
+
+        self.search.update(&self.lines);
+        if let Some(top) = self.top {
+            self.top = Some(top.min(self.last_top()));
+        }
+    }
+
+    /// Adds a line of the log if it matches the filter. Returns true if it has been added
+    fn push_line(&mut self, line: &str) -> bool {
+        let line = clean_line(line);
+        if self
+            .filter
+            .as_ref()
+            .is_none_or(|(_, regex)| regex.is_match(&line))
+        {
+            self.lines.push(line);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forgets what has been read, the log is read again from its beginning by `reload()`
+    fn clear(&mut self) {
+        self.lines.clear();
+        self.offset = 0;
+        self.partial.clear();
+        self.partial_shown = false;
+    }
+
+    /// Only shows the lines matching `filter`
+    pub fn set_filter(&mut self, filter: Option<(String, Regex)>) {
+        self.filter = filter;
+        self.top = None;
+        self.clear();
+        self.reload();
+    }
+
+    /// Looks for the typed pattern, starting from the first displayed line
+    pub fn submit_search(&mut self) {
+        if let Some(line) = self.search.submit(&self.lines, self.first_visible_line()) {
+            self.show_line(line);
+        }
+    }
+
+    pub fn next_match(&mut self) {
+        if let Some(line) = self.search.next() {
+            self.show_line(line);
+        }
+    }
+
+    pub fn previous_match(&mut self) {
+        if let Some(line) = self.search.previous() {
+            self.show_line(line);
+        }
+    }
+
+    /// Scrolls so that `line` is in the middle of the view
+    pub fn show_line(&mut self, line: usize) {
+        let top = line.saturating_sub(self.height / 2).min(self.last_top());
+        self.top = if top == self.last_top() {
+            None
+        } else {
+            Some(top)
+        };
+    }
+
+    /// First line displayed when the end of the log is displayed
+    fn last_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    /// First displayed line
+    #[must_use]
+    pub fn first_visible_line(&self) -> usize {
+        self.top.unwrap_or(self.last_top())
+    }
+
+    /// Scrolls by `delta` lines, reaching the end of the log means following it again
+    pub fn scroll(&mut self, delta: isize) {
+        let top = self
+            .first_visible_line()
+            .saturating_add_signed(delta)
+            .min(self.last_top());
+        self.top = if top == self.last_top() {
+            None
+        } else {
+            Some(top)
+        };
+    }
+
+    pub fn scroll_to_start(&mut self) {
+        self.top = Some(0);
+        if self.last_top() == 0 {
+            self.top = None;
+        }
+    }
+
+    pub fn follow(&mut self) {
+        self.top = None;
+    }
+
+    /// Scrolls to the beginning of the last boot. Returns false if no boot has been found
+    pub fn jump_to_boot(&mut self) -> bool {
+        match self
+            .lines
+            .iter()
+            .rposition(|line| line.starts_with(BOOT_MARKER) || line.contains(NETBSD_BANNER))
+        {
+            Some(idx) => {
+                self.top = Some(idx.min(self.last_top()));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_line_keeps_text_and_tabs() {
+        assert_eq!(clean_line("login:\tfoo"), "login:\tfoo");
+        assert_eq!(clean_line("été ✓"), "été ✓");
+    }
+
+    #[test]
+    fn clean_line_removes_escape_sequences() {
+        assert_eq!(clean_line("\x1b[1;32mOK\x1b[0m"), "OK");
+        assert_eq!(clean_line("\x1b]0;title\x07prompt$ "), "prompt$ ");
+        assert_eq!(clean_line("\x1b]0;title\x1b\\prompt$ "), "prompt$ ");
+    }
+
+    #[test]
+    fn clean_line_removes_control_characters() {
+        assert_eq!(clean_line("boot\r"), "boot");
+        assert_eq!(clean_line("a\x08\x00b\x07"), "ab");
+    }
+
+    #[test]
+    fn serial_logger_appends_the_output() {
+        let base_dir = std::env::temp_dir().join(format!("smolbsd-tui-log-{}", std::process::id()));
+        let base_dir = base_dir.to_str().unwrap();
+        std::fs::create_dir_all(base_dir).unwrap();
+        let socket_path = Path::new(base_dir).join("myvm.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
+
+        write_logging_marker(base_dir, "myvm").unwrap();
+        let logger = SerialLogger::start(base_dir, "myvm", &socket_path).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.write_all(b"login: ").unwrap();
+        let path = log_path(base_dir, "myvm");
+        for _ in 0..50 {
+            if lines_since_last_start(&path).unwrap() == ["login: "] {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(lines_since_last_start(&path).unwrap(), ["login: "]);
+        assert!(!logger.is_finished());
+
+        // The console is released when the logger is dropped
+        drop(logger);
+        let mut buf = [0u8; 16];
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        std::fs::remove_dir_all(base_dir).unwrap();
+    }
+}
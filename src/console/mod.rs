@@ -0,0 +1,418 @@
+//! Serial console of the VMs: connection to the QEMU chardev and terminal emulation
+
+pub mod keys;
+pub mod log;
+pub mod player;
+pub mod raw;
+pub mod record;
+pub mod script;
+pub mod search;
+
+use ratatui::crossterm::event::KeyEvent;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+
+use crate::console::record::Recorder;
+use crate::console::search::Search;
+use crate::events::AppEvent;
+
+/// Number of lines kept by the terminal emulator when they scroll off the screen
+const SCROLLBACK_LINES: usize = 1000;
+
+/// How often (in ms) the reader thread checks if the console has been closed
+const POLL_TIMEOUT_MS: i32 = 200;
+
+/// Connection to a serial console: a QEMU chardev Unix socket (`-serial unix:<path>,server`)
+/// or a PTY (`-serial pty`)
+enum Connection {
+    Socket(UnixStream),
+    Pty(File),
+}
+
+impl Connection {
+    fn open(path: &Path) -> Result<Self, String> {
+        let is_pty = std::fs::metadata(path)
+            .map_err(|err| format!("Failed to access {}: {err}", path.display()))?
+            .file_type()
+            .is_char_device();
+
+        if is_pty {
+            let pty = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .map_err(|err| format!("Failed to open {}: {err}", path.display()))?;
+            set_raw_mode(pty.as_raw_fd())
+                .map_err(|err| format!("Failed to set {} in raw mode: {err}", path.display()))?;
+            Ok(Connection::Pty(pty))
+        } else {
+            UnixStream::connect(path)
+                .map(Connection::Socket)
+                .map_err(|err| format!("Failed to connect to {}: {err}", path.display()))
+        }
+    }
+
+    fn try_clone(&self) -> Result<Self, String> {
+        match self {
+            Connection::Socket(stream) => stream.try_clone().map(Connection::Socket),
+            Connection::Pty(pty) => pty.try_clone().map(Connection::Pty),
+        }
+        .map_err(|err| format!("Failed to clone the console connection: {err}"))
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Socket(stream) => stream.read(buf),
+            Connection::Pty(pty) => pty.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Socket(stream) => stream.write(buf),
+            Connection::Pty(pty) => pty.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Connection::Socket(stream) => stream.flush(),
+            Connection::Pty(pty) => pty.flush(),
+        }
+    }
+}
+
+impl AsRawFd for Connection {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Connection::Socket(stream) => stream.as_raw_fd(),
+            Connection::Pty(pty) => pty.as_raw_fd(),
+        }
+    }
+}
+
+/// Text of the displayed row `row`, with the offset (in bytes) and the column of each cell
+pub fn row_text(screen: &vt100::Screen, row: u16) -> (String, Vec<(usize, u16)>) {
+    let mut text = String::new();
+    let mut cells = Vec::new();
+    for col in 0..screen.size().1 {
+        if let Some(cell) = screen.cell(row, col)
+            && !cell.is_wide_continuation()
+        {
+            cells.push((text.len(), col));
+            if cell.has_contents() {
+                text.push_str(cell.contents());
+            } else {
+                text.push(' ');
+            }
+        }
+    }
+    (text, cells)
+}
+
+/// Disables the line discipline of a PTY (echo, line buffering, ...), so that we see
+/// exactly what QEMU writes
+fn set_raw_mode(fd: RawFd) -> std::io::Result<()> {
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    unsafe { libc::cfmakeraw(&mut termios) };
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Serial console of a running VM.
+/// The output is read in a dedicated thread and sent as `AppEvent::ConsoleOutput`,
+/// which must be given to `process()`.
+pub struct Console {
+    pub vm_name: String,
+    /// Set when the connection has been closed, with the reason
+    pub closed: Option<String>,
+    /// Set in search mode: the output is paused and the scrollback can be browsed
+    pub search: Option<Search>,
+    /// Set while the session is recorded
+    pub recorder: Option<Recorder>,
+    /// Set when the recording failed
+    pub record_error: Option<String>,
+    /// Lines of the scrollback and of the screen, when the search mode was entered
+    history: Vec<String>,
+    /// Length of the scrollback, when the search mode was entered
+    scrollback_len: usize,
+    /// Output received in search mode, processed when leaving it
+    pending: Vec<u8>,
+    parser: vt100::Parser,
+    connection: Connection,
+    stop: Arc<AtomicBool>,
+}
+
+impl Console {
+    /// Connects to the serial console `path` of VM `vm_name`.
+    /// Its output is appended to `log` when QEMU doesn't log it (cf. `log::SerialLogger`).
+    pub fn open(
+        vm_name: &str,
+        path: &Path,
+        log: Option<PathBuf>,
+        tx: Sender<AppEvent>,
+    ) -> Result<Self, String> {
+        let connection = Connection::open(path)?;
+        let mut reader = connection.try_clone()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stop_clone = stop.clone();
+        let vm_name_clone = vm_name.to_owned();
+        std::thread::spawn(move || {
+            read_output(&mut reader, &stop_clone, vm_name_clone, log.as_deref(), tx)
+        });
+
+        Ok(Console {
+            vm_name: vm_name.to_owned(),
+            closed: None,
+            search: None,
+            recorder: None,
+            record_error: None,
+            history: Vec::new(),
+            scrollback_len: 0,
+            pending: Vec::new(),
+            // The real size is set by `resize()` when the console is rendered
+            parser: vt100::Parser::new(24, 80, SCROLLBACK_LINES),
+            connection,
+            stop,
+        })
+    }
+
+    /// Feeds the terminal emulator with the output of the VM
+    pub fn process(&mut self, data: &[u8]) {
+        if let Some(recorder) = self.recorder.as_mut()
+            && let Err(err) = recorder.output(data)
+        {
+            self.recorder = None;
+            self.record_error = Some(err);
+        }
+        if self.search.is_some() {
+            self.pending.extend_from_slice(data);
+        } else {
+            self.parser.process(data);
+        }
+    }
+
+    /// Enters the search mode, the user being prompted for a pattern
+    pub fn start_search(&mut self) {
+        self.history = self.history_lines();
+        self.search = Some(Search::prompt());
+    }
+
+    /// Leaves the search mode: back to the bottom of the scrollback, with the output received in the meantime
+    pub fn stop_search(&mut self) {
+        self.search = None;
+        self.history.clear();
+        self.parser.screen_mut().set_scrollback(0);
+        let pending = std::mem::take(&mut self.pending);
+        self.parser.process(&pending);
+    }
+
+    /// Looks for the typed pattern, starting from the first displayed line
+    pub fn submit_search(&mut self) {
+        let first_line = self.history_index(0);
+        if let Some(search) = self.search.as_mut()
+            && let Some(line) = search.submit(&self.history, first_line)
+        {
+            self.show_history_line(line);
+        }
+    }
+
+    pub fn next_match(&mut self) {
+        if let Some(line) = self.search.as_mut().and_then(Search::next) {
+            self.show_history_line(line);
+        }
+    }
+
+    pub fn previous_match(&mut self) {
+        if let Some(line) = self.search.as_mut().and_then(Search::previous) {
+            self.show_history_line(line);
+        }
+    }
+
+    /// Scrolls back (`delta` > 0) or forward in the scrollback
+    pub fn scroll(&mut self, delta: isize) {
+        let screen = self.parser.screen_mut();
+        let offset = screen.scrollback().saturating_add_signed(delta);
+        screen.set_scrollback(offset);
+    }
+
+    /// Highlighted columns of the displayed row `row`: `Some(true)` for the current match,
+    /// `Some(false)` for the other ones
+    #[must_use]
+    pub fn row_highlights(&self, row: u16) -> Vec<Option<bool>> {
+        let screen = self.parser.screen();
+        let mut highlights = vec![None; screen.size().1 as usize];
+        if let Some(search) = &self.search {
+            let (_, cells) = row_text(screen, row);
+            for (range, current) in search.line_matches(self.history_index(row)) {
+                for (_, col) in cells.iter().filter(|(byte, _)| range.contains(byte)) {
+                    if let Some(highlight) = highlights.get_mut(*col as usize) {
+                        *highlight = Some(current);
+                    }
+                }
+            }
+        }
+        highlights
+    }
+
+    /// Index in `history` of the displayed row `row`
+    fn history_index(&self, row: u16) -> usize {
+        (self.scrollback_len + row as usize).saturating_sub(self.parser.screen().scrollback())
+    }
+
+    /// Scrolls so that the line `line` of `history` is in the middle of the screen
+    fn show_history_line(&mut self, line: usize) {
+        let rows = self.parser.screen().size().0 as usize;
+        let offset = (self.scrollback_len + rows / 2).saturating_sub(line);
+        self.parser.screen_mut().set_scrollback(offset);
+    }
+
+    /// Text of the lines of the scrollback followed by the lines of the screen
+    fn history_lines(&mut self) -> Vec<String> {
+        let screen = self.parser.screen_mut();
+        // The offset is clamped to the length of the scrollback
+        screen.set_scrollback(usize::MAX);
+        self.scrollback_len = screen.scrollback();
+
+        let mut lines = Vec::new();
+        for offset in (1..=self.scrollback_len).rev() {
+            screen.set_scrollback(offset);
+            lines.push(row_text(screen, 0).0);
+        }
+        screen.set_scrollback(0);
+        lines.extend((0..screen.size().0).map(|row| row_text(screen, row).0));
+        lines
+    }
+
+    #[must_use]
+    pub fn screen(&self) -> &vt100::Screen {
+        self.parser.screen()
+    }
+
+    /// Resizes the terminal emulator, when the size of the console pane changed
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        if self.parser.screen().size() != (rows, cols) {
+            self.parser.screen_mut().set_size(rows, cols);
+            if let Some(recorder) = self.recorder.as_mut()
+                && let Err(err) = recorder.resize(rows, cols)
+            {
+                self.recorder = None;
+                self.record_error = Some(err);
+            }
+        }
+    }
+
+    /// Starts recording the session in `recordings/`, or stops the current recording
+    pub fn toggle_recording(&mut self, base_dir: &str) {
+        self.record_error = None;
+        if self.recorder.take().is_none() {
+            let (rows, cols) = self.parser.screen().size();
+            match Recorder::start(base_dir, &self.vm_name, rows, cols) {
+                Ok(recorder) => self.recorder = Some(recorder),
+                Err(err) => self.record_error = Some(err),
+            }
+        }
+    }
+
+    /// Sends a key to the VM
+    pub fn send_key(&mut self, key: KeyEvent) -> Result<(), String> {
+        let mut bytes = keys::key_to_bytes(key);
+        // In "application cursor" mode, the arrow keys are sent as "ESC O <x>" instead of "ESC [ <x>"
+        if self.parser.screen().application_cursor()
+            && let [0x1b, b'[', b'A'..=b'D'] = bytes.as_slice()
+        {
+            bytes[1] = b'O';
+        }
+        self.send(&bytes)
+    }
+
+    /// Sends raw bytes to the VM
+    pub fn send(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if let Some(reason) = &self.closed {
+            return Err(reason.clone());
+        }
+        self.connection
+            .write_all(bytes)
+            .and_then(|_| self.connection.flush())
+            .map_err(|err| format!("Failed to write to the console: {err}"))
+    }
+}
+
+impl Drop for Console {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Connection::Socket(stream) = &self.connection {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+/// Reads the output of the console until it is closed, either by the VM or by `stop`
+fn read_output(
+    reader: &mut Connection,
+    stop: &AtomicBool,
+    vm_name: String,
+    log: Option<&Path>,
+    tx: Sender<AppEvent>,
+) {
+    let mut buf = [0u8; 4096];
+    let error = loop {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut pollfd = libc::pollfd {
+            fd: reader.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pollfd, 1, POLL_TIMEOUT_MS) } {
+            0 => continue,
+            ret if ret < 0 => {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break Some(err.to_string());
+            }
+            _ => {}
+        }
+
+        match reader.read(&mut buf) {
+            Ok(0) => break None,
+            Ok(len) => {
+                if let Some(log) = log {
+                    let _ = log::append(log, &buf[..len]);
+                }
+                tx.send(AppEvent::ConsoleOutput {
+                    vm_name: vm_name.clone(),
+                    data: buf[..len].to_vec(),
+                })
+                .unwrap()
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => break Some(err.to_string()),
+        }
+    };
+
+    if !stop.load(Ordering::Relaxed) {
+        tx.send(AppEvent::ConsoleClosed { vm_name, error }).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+//! Player of the console recordings (asciicast v2 files)
+
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::events::AppEvent;
+
+/// Playback speeds, `<+>` and `<->` going to the next and previous ones
+pub const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// Index of the normal speed in `SPEEDS`
+const NORMAL_SPEED: usize = 2;
+
+/// Time (in ms) between two frames of the playback
+const FRAME_MS: u64 = 50;
+
+enum Event {
+    Output(String),
+    Resize { rows: u16, cols: u16 },
+}
+
+/// Replays a recording in a terminal emulator.
+/// While it exists, a thread sends an `AppEvent::PlayerTick` at each frame, which must call `advance()`.
+pub struct Player {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    /// Size of the terminal at the beginning of the recording
+    size: (u16, u16),
+    /// Events and their time (in seconds), sorted by time
+    events: Vec<(f64, Event)>,
+    /// Index of the next event to play
+    next: usize,
+    /// Current time of the recording (in seconds)
+    pub position: f64,
+    pub duration: f64,
+    /// Index of the playback speed in `SPEEDS`
+    pub speed: usize,
+    pub paused: bool,
+    last_update: Instant,
+    parser: vt100::Parser,
+    stop: Arc<AtomicBool>,
+}
+
+impl Player {
+    pub fn open(path: &Path, tx: Sender<AppEvent>) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let invalid =
+            |line: usize, reason: &str| format!("{}, line {}: {reason}", path.display(), line + 1);
+
+        let mut lines = data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty());
+        let header: Value = lines
+            .next()
+            .and_then(|(_, line)| serde_json::from_str(line).ok())
+            .ok_or(invalid(0, "invalid asciicast header"))?;
+        if header["version"] != 2 {
+            return Err(invalid(0, "only asciicast v2 files are supported"));
+        }
+        let dimension = |key: &str| {
+            header[key]
+                .as_u64()
+                .and_then(|value| u16::try_from(value).ok())
+                .ok_or(invalid(0, &format!("invalid '{key}'")))
+        };
+        let size = (dimension("height")?, dimension("width")?);
+        let idle_time_limit = header["idle_time_limit"].as_f64();
+
+        let mut events = Vec::new();
+        // The pauses longer than `idle_time_limit` are shortened
+        let (mut recorded_time, mut time) = (0.0, 0.0);
+        for (idx, line) in lines {
+            let event: (f64, String, String) =
+                serde_json::from_str(line).map_err(|err| invalid(idx, &err.to_string()))?;
+            let pause = (event.0 - recorded_time).max(0.0);
+            time += idle_time_limit.map_or(pause, |limit| pause.min(limit));
+            recorded_time = event.0;
+            match event.1.as_str() {
+                "o" => events.push((time, Event::Output(event.2))),
+                "r" => {
+                    if let Some((cols, rows)) = event.2.split_once('x')
+                        && let (Ok(cols), Ok(rows)) = (cols.parse(), rows.parse())
+                    {
+                        events.push((time, Event::Resize { rows, cols }));
+                    }
+                }
+                // Input, markers, ...
+                _ => {}
+            }
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop.clone();
+        std::thread::spawn(move || {
+            while !stop_clone.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(FRAME_MS));
+                if tx.send(AppEvent::PlayerTick).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Player {
+            path: path.to_owned(),
+            title: header["title"].as_str().map(str::to_owned),
+            size,
+            duration: events.last().map_or(0.0, |(time, _)| *time),
+            events,
+            next: 0,
+            position: 0.0,
+            speed: NORMAL_SPEED,
+            paused: false,
+            last_update: Instant::now(),
+            parser: vt100::Parser::new(size.0, size.1, 0),
+            stop,
+        })
+    }
+
+    /// Plays the events up to the current time. The playback is paused at the end of the recording
+    pub fn advance(&mut self) {
+        let elapsed = self.last_update.elapsed().as_secs_f64();
+        self.last_update = Instant::now();
+        if !self.paused {
+            self.position = (self.position + elapsed * SPEEDS[self.speed]).min(self.duration);
+            self.play_events();
+            if self.position >= self.duration {
+                self.paused = true;
+            }
+        }
+    }
+
+    /// Moves forward (`delta` > 0) or backward in the recording
+    pub fn seek(&mut self, delta: f64) {
+        let position = (self.position + delta).clamp(0.0, self.duration);
+        if position < self.position {
+            // The terminal emulator can't go back, the recording is played again from its beginning
+            self.parser = vt100::Parser::new(self.size.0, self.size.1, 0);
+            self.next = 0;
+        }
+        self.position = position;
+        self.play_events();
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused && self.position >= self.duration {
+            self.seek(-self.duration);
+        }
+        self.paused = !self.paused;
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    fn play_events(&mut self) {
+        while let Some((time, event)) = self.events.get(self.next)
+            && *time <= self.position
+        {
+            match event {
+                Event::Output(data) => self.parser.process(data.as_bytes()),
+                Event::Resize { rows, cols } => self.parser.screen_mut().set_size(*rows, *cols),
+            }
+            self.next += 1;
+        }
+    }
+
+    #[must_use]
+    pub fn screen(&self) -> &vt100::Screen {
+        self.parser.screen()
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens a recording written to a temporary file
+    fn open(name: &str, content: &str) -> Result<Player, String> {
+        let path = std::env::temp_dir().join(format!(
+            "smolbsd-tui-player-{}-{name}.cast",
+            std::process::id()
+        ));
+        std::fs::write(&path, content).unwrap();
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let player = Player::open(&path, tx);
+        std::fs::remove_file(&path).unwrap();
+        player
+    }
+
+    #[test]
+    fn open_and_seek() {
+        let mut player = open(
+            "valid",
+            concat!(
+                r#"{"version": 2, "width": 80, "height": 24, "title": "myvm"}"#,
+                "\n",
+                r#"[0.5, "o", "login: "]"#,
+                "\n\n",
+                r#"[1.0, "i", "root\r"]"#,
+                "\n",
+                r#"[2.0, "o", "root"]"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        assert_eq!(player.title.as_deref(), Some("myvm"));
+        assert_eq!(player.size, (24, 80));
+        assert_eq!(player.events.len(), 2);
+        assert_eq!(player.duration, 2.0);
+
+        player.seek(1.0);
+        assert_eq!(player.screen().contents(), "login: ");
+        player.seek(5.0);
+        assert_eq!(player.screen().contents(), "login: root");
+    }
+
+    #[test]
+    fn idle_time_limit_shortens_pauses() {
+        let player = open(
+            "idle",
+            concat!(
+                r#"{"version": 2, "width": 80, "height": 24, "idle_time_limit": 1.5}"#,
+                "\n",
+                r#"[1.0, "o", "a"]"#,
+                "\n",
+                r#"[11.0, "r", "100x30"]"#,
+                "\n",
+                r#"[12.0, "o", "b"]"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        assert_eq!(player.duration, 3.5);
+        assert!(matches!(
+            player.events[1],
+            (
+                2.5,
+                Event::Resize {
+                    rows: 30,
+                    cols: 100
+                }
+            )
+        ));
+    }
+
+    #[test]
+    fn invalid_recordings() {
+        let err = |name, content| open(name, content).err().unwrap();
+        assert!(err("empty", "").ends_with("line 1: invalid asciicast header"));
+        assert!(err("header", "not json\n").ends_with("line 1: invalid asciicast header"));
+        assert!(
+            err("v1", r#"{"version": 1, "width": 80, "height": 24}"#)
+                .ends_with("line 1: only asciicast v2 files are supported")
+        );
+        assert!(
+            err("width", r#"{"version": 2, "width": 100000, "height": 24}"#)
+                .ends_with("line 1: invalid 'width'")
+        );
+        assert!(
+            err(
+                "event",
+                "{\"version\": 2, \"width\": 80, \"height\": 24}\n[1.0, \"o\"]\n"
+            )
+            .contains("line 2: ")
+        );
+    }
+}
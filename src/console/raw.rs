@@ -0,0 +1,127 @@
+//! Raw console: the terminal is handed over to the serial console of a VM
+
+use ratatui::crossterm::{
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+use crate::console::{Connection, log};
+
+/// Leaves the alternate screen and connects stdin/stdout to the serial console `path`
+/// until `escape` is typed or the console is closed. Its output is appended to `log` when QEMU
+/// doesn't log it.
+///
+/// ⚠️ the terminal must be in raw mode and nothing else must read stdin in the meantime
+pub fn attach(
+    vm_name: &str,
+    path: &Path,
+    log: Option<&Path>,
+    escape: &[u8],
+    escape_name: &str,
+) -> Result<(), String> {
+    let mut connection = Connection::open(path)?;
+    let mut stdout = std::io::stdout();
+
+    execute!(stdout, LeaveAlternateScreen)
+        .map_err(|err| format!("Failed to leave the alternate screen: {err}"))?;
+    // Errors are written on the terminal, so the alternate screen must be restored in any case
+    let res = forward(vm_name, &mut connection, log, escape, escape_name);
+    execute!(stdout, EnterAlternateScreen)
+        .map_err(|err| format!("Failed to enter the alternate screen: {err}"))?;
+
+    res
+}
+
+fn forward(
+    vm_name: &str,
+    connection: &mut Connection,
+    log: Option<&Path>,
+    escape: &[u8],
+    escape_name: &str,
+) -> Result<(), String> {
+    let mut stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    let write_err = |err: std::io::Error| format!("Failed to write to the terminal: {err}");
+
+    write!(
+        stdout,
+        "\r\nConnected to the console of VM '{vm_name}', press {escape_name} to go back to the TUI\r\n"
+    )
+    .and_then(|_| stdout.flush())
+    .map_err(write_err)?;
+
+    let mut buf = [0u8; 4096];
+    // Beginning of the escape sequence typed so far (it can be split across reads)
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let mut pollfds = [
+            libc::pollfd {
+                fd: stdin.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: connection.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        if unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, -1) } < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(format!("Failed to wait for the console: {err}"));
+        }
+
+        if pollfds[1].revents != 0 {
+            match connection.read(&mut buf) {
+                Ok(0) => return Err("Connection closed by QEMU".to_owned()),
+                Ok(len) => {
+                    if let Some(log) = log {
+                        let _ = log::append(log, &buf[..len]);
+                    }
+                    stdout
+                        .write_all(&buf[..len])
+                        .and_then(|_| stdout.flush())
+                        .map_err(write_err)?
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(format!("Failed to read the console: {err}")),
+            }
+        }
+
+        if pollfds[0].revents != 0 {
+            let len = stdin
+                .read(&mut buf)
+                .map_err(|err| format!("Failed to read the terminal: {err}"))?;
+            pending.extend_from_slice(&buf[..len]);
+
+            if let Some(idx) = pending
+                .windows(escape.len())
+                .position(|window| window == escape)
+            {
+                // What was typed before the escape sequence still goes to the VM
+                let _ = connection.write_all(&pending[..idx]);
+                write!(stdout, "\r\n")
+                    .and_then(|_| stdout.flush())
+                    .map_err(write_err)?;
+                return Ok(());
+            }
+
+            // Keep what could be the beginning of the escape sequence
+            let keep = (1..escape.len())
+                .rev()
+                .find(|len| pending.ends_with(&escape[..*len]))
+                .unwrap_or(0);
+            let send = pending.len() - keep;
+            connection
+                .write_all(&pending[..send])
+                .map_err(|err| format!("Failed to write to the console: {err}"))?;
+            pending.drain(..send);
+        }
+    }
+}
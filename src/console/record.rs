@@ -0,0 +1,97 @@
+//! Recording of console sessions as asciicast v2 files (https://docs.asciinema.org/manual/asciicast/v2/),
+//! in `recordings/`
+
+use serde_json::json;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::vm::helpers::{files_in_directory, format_utc};
+
+/// Directory of the console recordings, in the base directory
+pub const RECORDINGS_DIR: &str = "recordings";
+
+/// Recordings in `recordings/`, sorted by name (ie. by VM then by date)
+pub fn recording_paths(base_dir: &str) -> Vec<PathBuf> {
+    let mut recordings: Vec<PathBuf> = files_in_directory(&format!("{base_dir}{RECORDINGS_DIR}"))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|file| file.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "cast")
+        })
+        .collect();
+    recordings.sort();
+    recordings
+}
+
+/// Recording of the output of a console, written as it is received
+pub struct Recorder {
+    pub path: PathBuf,
+    file: File,
+    started: Instant,
+    /// End of the output which isn't a complete UTF-8 character yet
+    incomplete: Vec<u8>,
+}
+
+impl Recorder {
+    /// Creates `recordings/<vm_name>-<date>.cast` for a terminal of `rows` x `cols`
+    pub fn start(base_dir: &str, vm_name: &str, rows: u16, cols: u16) -> Result<Self, String> {
+        let dir = Path::new(base_dir).join(RECORDINGS_DIR);
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| format!("Failed to create {}: {err}", dir.display()))?;
+
+        let now = SystemTime::now();
+        let path = dir.join(format!(
+            "{vm_name}-{}.cast",
+            format_utc(now).replace([' ', ':'], "-")
+        ));
+        let mut file = File::create(&path)
+            .map_err(|err| format!("Failed to create {}: {err}", path.display()))?;
+        let header = json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": now.duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0),
+            "title": format!("Console of '{vm_name}'"),
+        });
+        writeln!(file, "{header}")
+            .map_err(|err| format!("Failed to write to {}: {err}", path.display()))?;
+
+        Ok(Recorder {
+            path,
+            file,
+            started: Instant::now(),
+            incomplete: Vec::new(),
+        })
+    }
+
+    /// Records output of the console
+    pub fn output(&mut self, data: &[u8]) -> Result<(), String> {
+        self.incomplete.extend_from_slice(data);
+        // An UTF-8 character may be split between 2 reads
+        let complete = match std::str::from_utf8(&self.incomplete) {
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            _ => self.incomplete.len(),
+        };
+        if complete == 0 {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.incomplete[..complete]).into_owned();
+        self.incomplete.drain(..complete);
+        self.write_event("o", &text)
+    }
+
+    /// Records the resizing of the terminal
+    pub fn resize(&mut self, rows: u16, cols: u16) -> Result<(), String> {
+        self.write_event("r", &format!("{cols}x{rows}"))
+    }
+
+    fn write_event(&mut self, code: &str, data: &str) -> Result<(), String> {
+        let event = json!([self.started.elapsed().as_secs_f64(), code, data]);
+        writeln!(self.file, "{event}")
+            .map_err(|err| format!("Failed to write to {}: {err}", self.path.display()))
+    }
+}
@@ -0,0 +1,408 @@
+//! Console scripts: files of `scripts/<vm_name>/` pairing patterns expected on the serial console
+//! with input sent to the VM, ie. to log in and run setup commands.
+//!
+//! Each line of a script is a command:
+//! - `expect <regex>`: waits for the console output to match `regex`
+//! - `send <text>`: sends `text` (`\r`, `\n`, `\t`, `\e` and `\\` are unescaped)
+//! - `sendline <text>`: sends `text` followed by Enter
+//! - `timeout <seconds>`: timeout of the following `expect` commands (30s by default)
+//! - `sleep <seconds>`: waits before the next command
+//!
+//! Empty lines and lines starting with `#` are ignored.
+
+use regex::Regex;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::console::{
+    Connection, POLL_TIMEOUT_MS,
+    log::{self, clean_line},
+};
+use crate::events::AppEvent;
+use crate::vm::helpers::files_in_directory;
+
+/// Directory of the console scripts, in the base directory (one sub-directory per VM)
+pub const SCRIPTS_DIR: &str = "scripts";
+
+/// Timeout of the `expect` commands when the script doesn't set one
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Size (in bytes) of the console output kept while waiting for a pattern
+const MAX_OUTPUT: usize = 64 << 10;
+
+/// Scripts of VM `vm_name`, sorted by name
+pub fn script_paths(base_dir: &str, vm_name: &str) -> Vec<PathBuf> {
+    let mut scripts: Vec<PathBuf> =
+        files_in_directory(&format!("{base_dir}{SCRIPTS_DIR}/{vm_name}"))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|file| file.path())
+            .filter(|path| path.is_file())
+            .collect();
+    scripts.sort();
+    scripts
+}
+
+enum Step {
+    Expect { regex: Regex, timeout: Duration },
+    Send(Vec<u8>),
+    Sleep(Duration),
+}
+
+/// Parses a script, returning its steps with their line
+fn parse(data: &str) -> Result<Vec<(String, Step)>, String> {
+    let mut steps = Vec::new();
+    let mut timeout = DEFAULT_TIMEOUT;
+    let seconds = |value: &str, idx: usize| {
+        value
+            .parse::<f64>()
+            .ok()
+            .and_then(|value| Duration::try_from_secs_f64(value).ok())
+            .ok_or(format!("line {}: invalid duration '{value}'", idx + 1))
+    };
+
+    for (idx, line) in data.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let step = match command {
+            "expect" => Step::Expect {
+                regex: Regex::new(argument)
+                    .map_err(|err| format!("line {}: invalid regex: {err}", idx + 1))?,
+                timeout,
+            },
+            "send" => Step::Send(unescape(argument)),
+            "sendline" => Step::Send([unescape(argument), vec![b'\r']].concat()),
+            "sleep" => Step::Sleep(seconds(argument.trim(), idx)?),
+            "timeout" => {
+                timeout = seconds(argument.trim(), idx)?;
+                continue;
+            }
+            command => return Err(format!("line {}: unknown command '{command}'", idx + 1)),
+        };
+        steps.push((line.to_owned(), step));
+    }
+    Ok(steps)
+}
+
+fn unescape(text: &str) -> Vec<u8> {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('r') => res.push('\r'),
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                Some('e') => res.push('\x1b'),
+                Some(c) => res.push(c),
+                None => res.push('\\'),
+            },
+            c => res.push(c),
+        }
+    }
+    res.into_bytes()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Passed,
+    Failed(String),
+}
+
+/// Run of a script on the serial console of a VM, in a dedicated thread which sends
+/// `AppEvent::ScriptProgress` and `AppEvent::ScriptFinished`
+pub struct ScriptRun {
+    /// File name of the script
+    pub name: String,
+    /// Line and status of each step
+    pub steps: Vec<(String, StepStatus)>,
+    /// Set when the script has ended, with the error if it failed
+    pub result: Option<Result<(), String>>,
+    pub started: Instant,
+    stop: Arc<AtomicBool>,
+}
+
+impl ScriptRun {
+    /// Runs the script `name` of VM `vm_name` on its serial console `serial_path`.
+    /// The console only sends what is written after the connection, so `output` is the output
+    /// already written (ie. the log since the VM was started) the first `expect` can match.
+    /// What is read is appended to `log` when QEMU doesn't log the console.
+    pub fn start(
+        base_dir: &str,
+        vm_name: &str,
+        name: &str,
+        serial_path: &Path,
+        output: String,
+        log: Option<PathBuf>,
+        tx: Sender<AppEvent>,
+    ) -> Result<Self, String> {
+        let path = Path::new(base_dir)
+            .join(SCRIPTS_DIR)
+            .join(vm_name)
+            .join(name);
+        let data = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let steps = parse(&data).map_err(|err| format!("{}, {err}", path.display()))?;
+        let mut connection = Connection::open(serial_path)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop.clone();
+        let vm_name_clone = vm_name.to_owned();
+        let statuses = steps
+            .iter()
+            .map(|(line, _)| (line.clone(), StepStatus::Pending))
+            .collect();
+        std::thread::spawn(move || {
+            let result = run(
+                &mut connection,
+                &steps,
+                output,
+                log.as_deref(),
+                &stop_clone,
+                &vm_name_clone,
+                &tx,
+            );
+            tx.send(AppEvent::ScriptFinished {
+                vm_name: vm_name_clone,
+                result,
+            })
+            .unwrap();
+        });
+
+        Ok(ScriptRun {
+            name: name.to_owned(),
+            steps: statuses,
+            result: None,
+            started: Instant::now(),
+            stop,
+        })
+    }
+
+    /// Stops the script, its current step fails
+    pub fn abort(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Short description of the progress, ie. "setup: 3/7"
+    #[must_use]
+    pub fn progress(&self) -> String {
+        let done = self
+            .steps
+            .iter()
+            .filter(|(_, status)| *status == StepStatus::Passed)
+            .count();
+        match &self.result {
+            None => format!("{}: {done}/{}", self.name, self.steps.len()),
+            Some(Ok(())) => format!("{}: passed", self.name),
+            Some(Err(_)) => format!("{}: failed ({done}/{})", self.name, self.steps.len()),
+        }
+    }
+}
+
+impl Drop for ScriptRun {
+    fn drop(&mut self) {
+        self.abort();
+    }
+}
+
+/// Runs the steps of a script, sending their status. Returns the error of the failed step, if any
+fn run(
+    connection: &mut Connection,
+    steps: &[(String, Step)],
+    mut output: String,
+    log: Option<&Path>,
+    stop: &AtomicBool,
+    vm_name: &str,
+    tx: &Sender<AppEvent>,
+) -> Result<(), String> {
+    let progress = |step: usize, status: StepStatus| {
+        tx.send(AppEvent::ScriptProgress {
+            vm_name: vm_name.to_owned(),
+            step,
+            status,
+        })
+        .unwrap()
+    };
+    for (idx, (_, step)) in steps.iter().enumerate() {
+        progress(idx, StepStatus::Running);
+        let res = match step {
+            Step::Expect { regex, timeout } => {
+                expect(connection, &mut output, log, regex, *timeout, stop)
+            }
+            Step::Send(bytes) => connection
+                .write_all(bytes)
+                .and_then(|_| connection.flush())
+                .map_err(|err| format!("Failed to write to the console: {err}")),
+            Step::Sleep(duration) => {
+                let deadline = Instant::now() + *duration;
+                while Instant::now() < deadline && !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(
+                        deadline
+                            .saturating_duration_since(Instant::now())
+                            .min(Duration::from_millis(POLL_TIMEOUT_MS as u64)),
+                    );
+                }
+                if stop.load(Ordering::Relaxed) {
+                    Err("aborted".to_owned())
+                } else {
+                    Ok(())
+                }
+            }
+        };
+        match res {
+            Ok(()) => progress(idx, StepStatus::Passed),
+            Err(err) => {
+                progress(idx, StepStatus::Failed(err.clone()));
+                return Err(format!("step {}: {err}", idx + 1));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the console until its output (without the escape sequences) matches `regex`.
+/// `output` keeps what has been read after the match, for the next `expect`.
+fn expect(
+    connection: &mut Connection,
+    output: &mut String,
+    log: Option<&Path>,
+    regex: &Regex,
+    timeout: Duration,
+    stop: &AtomicBool,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 4096];
+    loop {
+        let text = output
+            .lines()
+            .map(clean_line)
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Some(m) = regex.find(&text) {
+            *output = text[m.end()..].to_owned();
+            return Ok(());
+        }
+        if stop.load(Ordering::Relaxed) {
+            return Err("aborted".to_owned());
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(format!(
+                "'{regex}' not seen after {}s",
+                timeout.as_secs_f64()
+            ));
+        }
+
+        let mut pollfd = libc::pollfd {
+            fd: connection.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let poll_timeout = remaining.as_millis().min(POLL_TIMEOUT_MS as u128) as i32;
+        match unsafe { libc::poll(&mut pollfd, 1, poll_timeout) } {
+            0 => continue,
+            ret if ret < 0 => {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err.to_string());
+            }
+            _ => {}
+        }
+        match connection.read(&mut buf) {
+            Ok(0) => return Err("console closed by QEMU".to_owned()),
+            Ok(len) => {
+                if let Some(log) = log {
+                    let _ = log::append(log, &buf[..len]);
+                }
+                output.push_str(&String::from_utf8_lossy(&buf[..len]));
+                if output.len() > MAX_OUTPUT {
+                    let mut start = output.len() - MAX_OUTPUT;
+                    while !output.is_char_boundary(start) {
+                        start += 1;
+                    }
+                    output.drain(..start);
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_steps() {
+        let steps = parse(concat!(
+            "# Log in\n",
+            "\n",
+            "expect login: $\n",
+            "sendline root\n",
+            "timeout 2.5\n",
+            "  expect # $\n",
+            "send ls\\r\n",
+            "sleep 1\n",
+        ))
+        .unwrap();
+        let lines: Vec<&str> = steps.iter().map(|(line, _)| line.as_str()).collect();
+        assert_eq!(
+            lines,
+            [
+                "expect login: $",
+                "sendline root",
+                "expect # $",
+                "send ls\\r",
+                "sleep 1"
+            ]
+        );
+        assert!(matches!(
+            &steps[0].1,
+            Step::Expect { regex, timeout } if regex.as_str() == "login: $" && *timeout == DEFAULT_TIMEOUT
+        ));
+        assert!(matches!(&steps[1].1, Step::Send(bytes) if bytes == b"root\r"));
+        assert!(matches!(
+            &steps[2].1,
+            Step::Expect { timeout, .. } if *timeout == Duration::from_millis(2500)
+        ));
+        assert!(matches!(&steps[3].1, Step::Send(bytes) if bytes == b"ls\r"));
+        assert!(
+            matches!(&steps[4].1, Step::Sleep(duration) if *duration == Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |data| parse(data).err().unwrap();
+        assert_eq!(
+            err("expect login:\nwait 5\n"),
+            "line 2: unknown command 'wait'"
+        );
+        assert!(err("expect (login").starts_with("line 1: invalid regex: "));
+        assert_eq!(err("sleep soon"), "line 1: invalid duration 'soon'");
+        assert_eq!(err("timeout -1"), "line 1: invalid duration '-1'");
+        assert_eq!(err("sleep"), "line 1: invalid duration ''");
+    }
+
+    #[test]
+    fn unescape_sequences() {
+        assert_eq!(unescape(r"a\r\n\tb"), b"a\r\n\tb");
+        assert_eq!(unescape(r"\e[A"), b"\x1b[A");
+        assert_eq!(unescape(r"C:\\dir\q"), b"C:\\dirq");
+        assert_eq!(unescape("end\\"), b"end\\");
+        assert_eq!(unescape("été"), "été".as_bytes());
+    }
+}
@@ -0,0 +1,194 @@
+//! Regex search in the console and in the console logs
+
+use regex::Regex;
+use std::ops::Range;
+
+/// A match of the search: index of the line and range (in bytes) in this line
+pub struct Match {
+    pub line: usize,
+    pub range: Range<usize>,
+}
+
+/// Search state of a console or of a log viewer
+#[derive(Default)]
+pub struct Search {
+    /// Pattern being typed, `None` when the user isn't typing a pattern
+    pub input: Option<String>,
+    pub pattern: String,
+    regex: Option<Regex>,
+    /// Set when `pattern` isn't a valid regex
+    pub error: Option<String>,
+    /// Matches sorted by line and position
+    pub matches: Vec<Match>,
+    /// Index of the current match in `matches`
+    pub current: usize,
+}
+
+impl Search {
+    /// A search waiting for its pattern to be typed
+    pub fn prompt() -> Self {
+        Search {
+            input: Some(String::new()),
+            ..Search::default()
+        }
+    }
+
+    /// Uses the typed pattern (an empty pattern clears the search) and looks for it in `lines`.
+    /// Returns the line of the first match after `from_line`, if any.
+    pub fn submit(&mut self, lines: &[String], from_line: usize) -> Option<usize> {
+        self.pattern = self.input.take().unwrap_or_default();
+        self.regex = None;
+        self.error = None;
+        if !self.pattern.is_empty() {
+            match Regex::new(&self.pattern) {
+                Ok(regex) => self.regex = Some(regex),
+                Err(err) => self.error = Some(err.to_string()),
+            }
+        }
+
+        self.update(lines);
+        self.current = self
+            .matches
+            .iter()
+            .position(|m| m.line >= from_line)
+            .unwrap_or(0);
+        self.current_line()
+    }
+
+    /// Looks for the pattern in `lines` again, ie. when they changed
+    pub fn update(&mut self, lines: &[String]) {
+        self.matches.clear();
+        if let Some(regex) = &self.regex {
+            for (idx, line) in lines.iter().enumerate() {
+                self.matches.extend(regex.find_iter(line).map(|m| Match {
+                    line: idx,
+                    range: m.range(),
+                }));
+            }
+        }
+        self.current = self.current.min(self.matches.len().saturating_sub(1));
+    }
+
+    /// Line of the current match
+    #[must_use]
+    pub fn current_line(&self) -> Option<usize> {
+        self.matches.get(self.current).map(|m| m.line)
+    }
+
+    /// Goes to the next match (wrapping around) and returns its line
+    pub fn next(&mut self) -> Option<usize> {
+        if !self.matches.is_empty() {
+            self.current = (self.current + 1) % self.matches.len();
+        }
+        self.current_line()
+    }
+
+    /// Goes to the previous match (wrapping around) and returns its line
+    pub fn previous(&mut self) -> Option<usize> {
+        if !self.matches.is_empty() {
+            self.current = (self.current + self.matches.len() - 1) % self.matches.len();
+        }
+        self.current_line()
+    }
+
+    /// Ranges of the matches in line `line`, with a boolean telling if it is the current match
+    #[must_use]
+    pub fn line_matches(&self, line: usize) -> Vec<(Range<usize>, bool)> {
+        let start = self.matches.partition_point(|m| m.line < line);
+        self.matches[start..]
+            .iter()
+            .take_while(|m| m.line == line)
+            .enumerate()
+            .map(|(idx, m)| (m.range.clone(), start + idx == self.current))
+            .collect()
+    }
+
+    /// Short description of the search, ie. "/error 3/27"
+    #[must_use]
+    pub fn status(&self) -> Option<String> {
+        if let Some(input) = &self.input {
+            Some(format!("/{input}"))
+        } else if let Some(error) = &self.error {
+            Some(format!(
+                "/{}: {}",
+                self.pattern,
+                error.lines().last().unwrap_or("invalid regex")
+            ))
+        } else if self.pattern.is_empty() {
+            None
+        } else if self.matches.is_empty() {
+            Some(format!("/{} no match", self.pattern))
+        } else {
+            Some(format!(
+                "/{} {}/{}",
+                self.pattern,
+                self.current + 1,
+                self.matches.len()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submit(pattern: &str, lines: &[String], from_line: usize) -> (Search, Option<usize>) {
+        let mut search = Search::prompt();
+        search.input = Some(pattern.to_owned());
+        let line = search.submit(lines, from_line);
+        (search, line)
+    }
+
+    fn lines() -> Vec<String> {
+        ["error: a", "ok", "warning", "error: b error: c"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    #[test]
+    fn submit_starts_at_the_first_match_after_from_line() {
+        let (search, line) = submit("error", &lines(), 1);
+        assert_eq!(search.matches.len(), 3);
+        assert_eq!(line, Some(3));
+        assert_eq!(search.status().as_deref(), Some("/error 2/3"));
+        assert_eq!(search.line_matches(3), vec![(0..5, true), (9..14, false)]);
+    }
+
+    #[test]
+    fn next_and_previous_wrap_around() {
+        let (mut search, _) = submit("error", &lines(), 0);
+        assert_eq!(search.next(), Some(3));
+        assert_eq!(search.next(), Some(3));
+        assert_eq!(search.next(), Some(0));
+        assert_eq!(search.previous(), Some(3));
+    }
+
+    #[test]
+    fn no_match_and_empty_pattern() {
+        let (mut search, line) = submit("panic", &lines(), 0);
+        assert_eq!(line, None);
+        assert_eq!(search.next(), None);
+        assert_eq!(search.status().as_deref(), Some("/panic no match"));
+
+        let (search, line) = submit("", &lines(), 0);
+        assert_eq!(line, None);
+        assert_eq!(search.status(), None);
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let (search, line) = submit("error(", &lines(), 0);
+        assert_eq!(line, None);
+        assert!(search.error.is_some());
+        assert!(search.status().unwrap().starts_with("/error(: "));
+    }
+
+    #[test]
+    fn update_keeps_the_current_match_in_bounds() {
+        let (mut search, _) = submit("error", &lines(), 3);
+        search.next();
+        search.update(&lines()[..1]);
+        assert_eq!(search.current_line(), Some(0));
+    }
+}
@@ -1,5 +1,6 @@
 use notify::{Event, RecursiveMode, Result, Watcher};
 use std::{
+    os::unix::fs::FileTypeExt,
     path::{Path, PathBuf},
     sync::mpsc::{self, Sender},
 };
@@ -33,7 +34,9 @@ pub fn get_fs_events(app_tx: Sender<AppEvent>, base_dir: String) {
                 | notify::EventKind::Access(_)
                 | notify::EventKind::Other => {}
                 notify::EventKind::Create(create_kind) => {
-                    if create_kind == notify::event::CreateKind::File {
+                    // Depending on the platform, the creation of a Unix socket
+                    // can be reported as a file or as something else
+                    if create_kind != notify::event::CreateKind::Folder {
                         send_file_event(&base_dir, &app_tx, event.paths, FileOperation::Created)
                     }
                 }
@@ -112,7 +115,19 @@ fn send_file_event(
                 FileOperation::Deleted => Some(AppEvent::PidFileDeleted(vm_name)),
             }
         } else {
-            None
+            // Other files are only relevant if they are Unix sockets (ie. QMP sockets)
+            let filename = relative_filename.to_owned();
+            match operation {
+                FileOperation::Created
+                    if std::fs::symlink_metadata(&absolute_filename)
+                        .is_ok_and(|metadata| metadata.file_type().is_socket()) =>
+                {
+                    Some(AppEvent::SocketCreated(filename))
+                }
+                FileOperation::Created | FileOperation::Modified => None,
+                // A deleted file can't be inspected anymore, so we don't know if it was a socket
+                FileOperation::Deleted => Some(AppEvent::OtherFileDeleted(filename)),
+            }
         };
 
         if let Some(event) = event {
@@ -1,5 +1,22 @@
-use crate::{app::State, events::AppEvent, ui::Screen, vm::VmState};
+use crate::{
+    app::{State, activity::Severity},
+    console,
+    events::AppEvent,
+    ui::Screen,
+    vm::{
+        self, VmState,
+        helpers::format_bytes,
+        qmp::{self, HOLD_TIMES_MS, KEY_COMBINATIONS},
+    },
+};
 use ratatui::crossterm::event::{self, KeyCode};
+use std::path::Path;
+
+/// Number of lines scrolled by PgUp/PgDn in the startnb.sh output and in the activity log
+const SCROLL_PAGE: usize = 10;
+
+/// Number of seconds skipped by Left/Right in the console recordings player
+const SEEK_SECONDS: f64 = 5.0;
 
 pub fn handle(app: &mut State, event: AppEvent) -> Result<(), Box<dyn std::error::Error>> {
     match event {
@@ -22,11 +39,117 @@ pub fn handle(app: &mut State, event: AppEvent) -> Result<(), Box<dyn std::error
                         app.exit = true;
                     }
                     KeyCode::Char('s') => {
-                        app.start_stop_selected_vm();
+                        app.current_screen = app.start_stop_screen_for_selected_vm();
+                        if app.current_screen == Screen::List {
+                            app.start_stop_selected_vm();
+                        }
                     }
                     KeyCode::Char('d') => {
                         app.current_screen = Screen::DeleteConfirmation(false);
                     }
+                    KeyCode::Char('a') => {
+                        app.current_screen =
+                            app.qmp_screen_for_selected_vm(Screen::AttachDisk { selected: 0 });
+                    }
+                    KeyCode::Char('x') => {
+                        app.current_screen =
+                            app.qmp_screen_for_selected_vm(Screen::DetachDisk { selected: 0 });
+                    }
+                    KeyCode::Char('b') => {
+                        app.current_screen = app.qmp_screen_for_selected_vm(Screen::Balloon {
+                            target: String::new(),
+                        });
+                        if let Screen::Balloon { .. } = app.current_screen {
+                            app.query_balloon_of_selected_vm();
+                        }
+                    }
+                    KeyCode::Char('f') => {
+                        app.current_screen = app.qmp_screen_for_selected_vm(Screen::PortForwards {
+                            netdev: None,
+                            forwards: Vec::new(),
+                            selected: 0,
+                            input: None,
+                        });
+                        if let Screen::PortForwards { .. } = app.current_screen {
+                            app.list_port_forwards_of_selected_vm();
+                        }
+                    }
+                    KeyCode::Char('g') => {
+                        app.current_screen =
+                            app.agent_screen_for_selected_vm(Screen::ShutdownConfirmation(false));
+                    }
+                    KeyCode::Char('h') => {
+                        app.current_screen = app.qmp_screen_for_selected_vm(Screen::List);
+                        if app.current_screen == Screen::List
+                            && let Err(error) = app.hibernate_selected_vm()
+                        {
+                            app.current_screen = Screen::QmpFailed {
+                                vm_name: app
+                                    .selected_vm()
+                                    .map(|vm| vm.name.clone())
+                                    .unwrap_or_default(),
+                                error,
+                            }
+                        }
+                    }
+                    KeyCode::Char('H') => {
+                        app.current_screen = Screen::SavedStates { selected: 0 };
+                    }
+                    KeyCode::Char('i') => {
+                        app.current_screen = Screen::Images { selected: 0 };
+                    }
+                    KeyCode::Char('?') => {
+                        app.current_screen = Screen::Help;
+                    }
+                    KeyCode::Char('p') => {
+                        app.current_screen = app.qmp_port_issues_screen();
+                    }
+                    KeyCode::Char('c') => {
+                        app.current_screen = app.open_console_of_selected_vm();
+                    }
+                    KeyCode::Char('C') => {
+                        app.current_screen = app.request_raw_console_of_selected_vm();
+                    }
+                    KeyCode::Char('l') => {
+                        app.current_screen = app.open_log_of_selected_vm();
+                    }
+                    KeyCode::Char('A') => {
+                        app.current_screen = Screen::Activity {
+                            vm_filter: None,
+                            min_severity: Severity::Info,
+                            offset: 0,
+                        };
+                    }
+                    KeyCode::Char('e') => {
+                        app.current_screen = Screen::Scripts { selected: 0 };
+                    }
+                    KeyCode::Char('E') => {
+                        if let Some(vm) = app.selected_vm()
+                            && app.scripts.contains_key(&vm.name)
+                        {
+                            app.current_screen = Screen::Script {
+                                vm_name: vm.name.clone(),
+                            };
+                        }
+                    }
+                    KeyCode::Char('R') => {
+                        app.current_screen = Screen::Recordings { selected: 0 };
+                    }
+                    KeyCode::Char('o') => {
+                        if let Some(vm) = app.selected_vm() {
+                            app.current_screen = Screen::StartLog {
+                                vm_name: vm.name.clone(),
+                                offset: 0,
+                            };
+                        }
+                    }
+                    KeyCode::Char('k') => {
+                        app.current_screen = app.qmp_screen_for_selected_vm(Screen::SendKeys {
+                            selected: 0,
+                            hold_time: 0,
+                            custom: String::new(),
+                        });
+                    }
                     _ => {}
                 },
                 Screen::DeleteConfirmation(ok) => match key_event.code {
@@ -46,38 +169,750 @@ pub fn handle(app: &mut State, event: AppEvent) -> Result<(), Box<dyn std::error
                     }
                     _ => {}
                 },
+                Screen::Help => match key_event.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('?') => {
+                        app.current_screen = Screen::List;
+                    }
+                    _ => {}
+                },
+                Screen::ShutdownConfirmation(ok) => match key_event.code {
+                    KeyCode::Esc => {
+                        app.current_screen = Screen::List;
+                    }
+                    KeyCode::Left => app.current_screen = Screen::ShutdownConfirmation(true),
+                    KeyCode::Right => app.current_screen = Screen::ShutdownConfirmation(false),
+                    KeyCode::Tab => {
+                        app.current_screen = Screen::ShutdownConfirmation(!ok);
+                    }
+                    KeyCode::Enter => {
+                        if ok {
+                            app.shutdown_selected_vm_from_guest();
+                        }
+                        app.current_screen = Screen::List;
+                    }
+                    _ => {}
+                },
+                Screen::OvercommitConfirmation {
+                    ref vm_name,
+                    committed,
+                    total,
+                    ok,
+                } => match key_event.code {
+                    KeyCode::Esc => {
+                        app.current_screen = Screen::List;
+                    }
+                    KeyCode::Left | KeyCode::Right | KeyCode::Tab => {
+                        app.current_screen = Screen::OvercommitConfirmation {
+                            vm_name: vm_name.clone(),
+                            committed,
+                            total,
+                            ok: match key_event.code {
+                                KeyCode::Left => true,
+                                KeyCode::Right => false,
+                                _ => !ok,
+                            },
+                        };
+                    }
+                    KeyCode::Enter => {
+                        let vm_name = vm_name.clone();
+                        app.current_screen = Screen::List;
+                        if ok {
+                            app.activity.warning(
+                                &vm_name,
+                                &format!(
+                                    "Starting with an overcommitted memory ({} of {})",
+                                    format_bytes(committed),
+                                    format_bytes(total)
+                                ),
+                            );
+                            app.start_vm(&vm_name);
+                        }
+                    }
+                    _ => {}
+                },
                 Screen::StartNbFailed { .. } => match key_event.code {
                     KeyCode::Esc | KeyCode::Enter => {
                         app.current_screen = Screen::List;
                     }
                     _ => {}
                 },
-                Screen::KillFailed { .. } => match key_event.code {
+                Screen::KillFailed { .. }
+                | Screen::QmpUnavailable { .. }
+                | Screen::AgentUnavailable { .. }
+                | Screen::ConsoleUnavailable { .. }
+                | Screen::RecordingFailed { .. }
+                | Screen::ScriptFailed { .. }
+                | Screen::QmpFailed { .. } => match key_event.code {
                     KeyCode::Esc | KeyCode::Enter => {
                         app.current_screen = Screen::List;
                     }
                     _ => {}
                 },
+                Screen::Console => {
+                    let key_bytes = console::keys::key_to_bytes(key_event);
+                    if key_bytes == app.config.console_escape_bytes {
+                        app.console = None;
+                        app.current_screen = Screen::List;
+                    } else if let Some(console) = app.console.as_mut() {
+                        let page = console.screen().size().0 as isize;
+                        if let Some(search) = console.search.as_mut() {
+                            // Search mode
+                            if search.input.is_some() {
+                                if edit_search_input(&mut search.input, key_event.code) {
+                                    console.submit_search();
+                                }
+                            } else {
+                                match key_event.code {
+                                    KeyCode::Esc | KeyCode::Char('q') => console.stop_search(),
+                                    KeyCode::Char('/') => search.input = Some(String::new()),
+                                    KeyCode::Char('n') => console.next_match(),
+                                    KeyCode::Char('N') => console.previous_match(),
+                                    KeyCode::Up => console.scroll(1),
+                                    KeyCode::Down => console.scroll(-1),
+                                    KeyCode::PageUp => console.scroll(page),
+                                    KeyCode::PageDown => console.scroll(-page),
+                                    _ => {}
+                                }
+                            }
+                        } else if key_bytes == app.config.console_search_bytes {
+                            console.start_search();
+                        } else if key_bytes == app.config.console_record_bytes {
+                            console.toggle_recording(&app.base_dir);
+                        } else if let Err(err) = console.send_key(key_event) {
+                            console.closed.get_or_insert(err);
+                        }
+                    }
+                }
+                Screen::Log => {
+                    if let Some(log_view) = app.log_view.as_mut() {
+                        let page = log_view.height.max(1) as isize;
+                        if log_view.search.input.is_some() {
+                            if edit_search_input(&mut log_view.search.input, key_event.code) {
+                                log_view.submit_search();
+                            }
+                        } else {
+                            match key_event.code {
+                                KeyCode::Esc | KeyCode::Char('q') => {
+                                    app.log_view = None;
+                                    app.current_screen = Screen::List;
+                                }
+                                KeyCode::Up => log_view.scroll(-1),
+                                KeyCode::Down => log_view.scroll(1),
+                                KeyCode::PageUp => log_view.scroll(-page),
+                                KeyCode::PageDown => log_view.scroll(page),
+                                KeyCode::Home => log_view.scroll_to_start(),
+                                KeyCode::End => log_view.follow(),
+                                KeyCode::Char('b') => {
+                                    log_view.jump_to_boot();
+                                }
+                                KeyCode::Char('/') => log_view.search.input = Some(String::new()),
+                                KeyCode::Char('n') => log_view.next_match(),
+                                KeyCode::Char('N') => log_view.previous_match(),
+                                KeyCode::Char('f') => {
+                                    // Cycles through the saved filters, then no filter
+                                    let next_filter = match &log_view.filter {
+                                        Some((name, _)) => app
+                                            .config
+                                            .filters
+                                            .iter()
+                                            .position(|(filter_name, _)| filter_name == name)
+                                            .map_or(0, |idx| idx + 1),
+                                        None => 0,
+                                    };
+                                    log_view
+                                        .set_filter(app.config.filters.get(next_filter).cloned());
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                Screen::StartLog {
+                    ref vm_name,
+                    offset,
+                } => {
+                    let vm_name = vm_name.clone();
+                    let len = app
+                        .vms
+                        .iter()
+                        .find(|vm| vm.name == vm_name)
+                        .map_or(0, |vm| vm.start_log.len());
+                    let offset = match key_event.code {
+                        KeyCode::Up => Some(offset + 1),
+                        KeyCode::Down => Some(offset.saturating_sub(1)),
+                        KeyCode::PageUp => Some(offset + SCROLL_PAGE),
+                        KeyCode::PageDown => Some(offset.saturating_sub(SCROLL_PAGE)),
+                        KeyCode::Home => Some(len),
+                        KeyCode::End => Some(0),
+                        KeyCode::Esc | KeyCode::Char('q') => None,
+                        _ => Some(offset),
+                    };
+                    app.current_screen = match offset {
+                        Some(offset) => Screen::StartLog {
+                            vm_name,
+                            offset: offset.min(len),
+                        },
+                        None => Screen::List,
+                    };
+                }
+                Screen::Activity {
+                    ref vm_filter,
+                    min_severity,
+                    offset,
+                } => {
+                    let mut vm_filter = vm_filter.clone();
+                    let mut min_severity = min_severity;
+                    let mut offset = offset;
+                    match key_event.code {
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Up => offset += 1,
+                        KeyCode::Down => offset = offset.saturating_sub(1),
+                        KeyCode::PageUp => offset += SCROLL_PAGE,
+                        KeyCode::PageDown => offset = offset.saturating_sub(SCROLL_PAGE),
+                        KeyCode::Home => offset = usize::MAX,
+                        KeyCode::End => offset = 0,
+                        KeyCode::Char('v') => {
+                            // Cycles through the VMs, then no filter
+                            let next_vm = match &vm_filter {
+                                Some(name) => app
+                                    .vms
+                                    .iter()
+                                    .position(|vm| &vm.name == name)
+                                    .map_or(0, |idx| idx + 1),
+                                None => 0,
+                            };
+                            vm_filter = app.vms.get(next_vm).map(|vm| vm.name.clone());
+                            offset = 0;
+                        }
+                        KeyCode::Char('s') => {
+                            min_severity = min_severity.next();
+                            offset = 0;
+                        }
+                        _ => {}
+                    }
+                    if app.current_screen != Screen::List {
+                        let len = app
+                            .activity
+                            .filtered(vm_filter.as_deref(), min_severity)
+                            .count();
+                        app.current_screen = Screen::Activity {
+                            vm_filter,
+                            min_severity,
+                            offset: offset.min(len),
+                        };
+                    }
+                }
+                Screen::QmpPortIssues {
+                    ref issues,
+                    free_port,
+                    ok,
+                } => {
+                    let issues = issues.clone();
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Left | KeyCode::Right | KeyCode::Tab if free_port.is_some() => {
+                            app.current_screen = Screen::QmpPortIssues {
+                                issues,
+                                free_port,
+                                ok: match key_event.code {
+                                    KeyCode::Left => true,
+                                    KeyCode::Right => false,
+                                    _ => !ok,
+                                },
+                            }
+                        }
+                        KeyCode::Enter => {
+                            app.current_screen = Screen::List;
+                            if let Some(free_port) = free_port
+                                && ok
+                                && let Err(error) = app.set_qmp_port_of_selected_vm(free_port)
+                            {
+                                app.current_screen = Screen::QmpFailed {
+                                    vm_name: app
+                                        .selected_vm()
+                                        .map(|vm| vm.name.clone())
+                                        .unwrap_or_default(),
+                                    error,
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::PortForwards {
+                    ref netdev,
+                    ref forwards,
+                    selected,
+                    ref input,
+                } => {
+                    let netdev = netdev.clone();
+                    let forwards = forwards.clone();
+                    match (input.clone(), key_event.code) {
+                        // A new port forward is being typed
+                        (Some(_), KeyCode::Esc) => {
+                            app.current_screen = Screen::PortForwards {
+                                netdev,
+                                forwards,
+                                selected,
+                                input: None,
+                            }
+                        }
+                        (Some(mut input), KeyCode::Char(c)) => {
+                            input.push(c);
+                            app.current_screen = Screen::PortForwards {
+                                netdev,
+                                forwards,
+                                selected,
+                                input: Some(input),
+                            }
+                        }
+                        (Some(mut input), KeyCode::Backspace) => {
+                            input.pop();
+                            app.current_screen = Screen::PortForwards {
+                                netdev,
+                                forwards,
+                                selected,
+                                input: Some(input),
+                            }
+                        }
+                        (Some(input), KeyCode::Enter) => {
+                            app.add_port_forward_to_selected_vm(netdev.clone(), input);
+                            app.current_screen = Screen::PortForwards {
+                                netdev,
+                                forwards,
+                                selected,
+                                input: None,
+                            }
+                        }
+                        (Some(_), _) => {}
+                        // Browsing the port forwards
+                        (None, KeyCode::Esc) => {
+                            app.current_screen = Screen::List;
+                        }
+                        (None, KeyCode::Up) => {
+                            app.current_screen = Screen::PortForwards {
+                                netdev,
+                                forwards,
+                                selected: selected.saturating_sub(1),
+                                input: None,
+                            }
+                        }
+                        (None, KeyCode::Down) => {
+                            app.current_screen = Screen::PortForwards {
+                                selected: (selected + 1).min(forwards.len().saturating_sub(1)),
+                                netdev,
+                                forwards,
+                                input: None,
+                            }
+                        }
+                        (None, KeyCode::Char('a')) => {
+                            app.current_screen = Screen::PortForwards {
+                                netdev,
+                                forwards,
+                                selected,
+                                input: Some("tcp::".to_owned()),
+                            }
+                        }
+                        (None, KeyCode::Char('d') | KeyCode::Delete) => {
+                            if let Some(forward) = forwards.get(selected) {
+                                app.remove_port_forward_from_selected_vm(netdev, forward.clone());
+                            }
+                        }
+                        (None, KeyCode::Char('w')) => {
+                            if let Err(error) = app.save_port_forwards_of_selected_vm(&forwards) {
+                                app.current_screen = Screen::QmpFailed {
+                                    vm_name: app
+                                        .selected_vm()
+                                        .map(|vm| vm.name.clone())
+                                        .unwrap_or_default(),
+                                    error,
+                                }
+                            }
+                        }
+                        (None, _) => {}
+                    }
+                }
+                Screen::Images { selected } => match key_event.code {
+                    KeyCode::Esc => {
+                        app.current_screen = Screen::List;
+                    }
+                    KeyCode::Up => {
+                        app.current_screen = Screen::Images {
+                            selected: selected.saturating_sub(1),
+                        }
+                    }
+                    KeyCode::Down => {
+                        app.current_screen = Screen::Images {
+                            selected: (selected + 1).min(app.image_names().len().saturating_sub(1)),
+                        }
+                    }
+                    _ => {}
+                },
+                Screen::SavedStates { selected } => {
+                    let saved_states = app.saved_states();
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Up => {
+                            app.current_screen = Screen::SavedStates {
+                                selected: selected.saturating_sub(1),
+                            }
+                        }
+                        KeyCode::Down => {
+                            app.current_screen = Screen::SavedStates {
+                                selected: (selected + 1).min(saved_states.len().saturating_sub(1)),
+                            }
+                        }
+                        KeyCode::Char('d') | KeyCode::Delete => {
+                            if let Some(path) = saved_states.get(selected)
+                                && let Err(error) = app.delete_saved_state(path)
+                            {
+                                app.current_screen = Screen::QmpFailed {
+                                    vm_name: path
+                                        .file_stem()
+                                        .map(|vm_name| vm_name.to_string_lossy().into_owned())
+                                        .unwrap_or_default(),
+                                    error,
+                                }
+                            } else {
+                                app.current_screen = Screen::SavedStates {
+                                    selected: selected.min(saved_states.len().saturating_sub(2)),
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::Recordings { selected } => {
+                    let recordings = console::record::recording_paths(&app.base_dir);
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Up => {
+                            app.current_screen = Screen::Recordings {
+                                selected: selected.saturating_sub(1),
+                            }
+                        }
+                        KeyCode::Down => {
+                            app.current_screen = Screen::Recordings {
+                                selected: (selected + 1).min(recordings.len().saturating_sub(1)),
+                            }
+                        }
+                        KeyCode::Enter => {
+                            if let Some(path) = recordings.get(selected) {
+                                app.current_screen = app.play_recording(path);
+                            }
+                        }
+                        KeyCode::Char('d') | KeyCode::Delete => {
+                            if let Some(path) = recordings.get(selected)
+                                && let Err(err) = std::fs::remove_file(path)
+                            {
+                                app.current_screen = Screen::RecordingFailed {
+                                    error: format!("Failed to delete {}: {err}", path.display()),
+                                }
+                            } else {
+                                app.current_screen = Screen::Recordings {
+                                    selected: selected.min(recordings.len().saturating_sub(2)),
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::Scripts { selected } => {
+                    let vm_name = app
+                        .selected_vm()
+                        .map(|vm| vm.name.clone())
+                        .unwrap_or_default();
+                    let scripts = console::script::script_paths(&app.base_dir, &vm_name);
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Up => {
+                            app.current_screen = Screen::Scripts {
+                                selected: selected.saturating_sub(1),
+                            }
+                        }
+                        KeyCode::Down => {
+                            app.current_screen = Screen::Scripts {
+                                selected: (selected + 1).min(scripts.len().saturating_sub(1)),
+                            }
+                        }
+                        KeyCode::Enter => {
+                            if let Some(name) = scripts
+                                .get(selected)
+                                .and_then(|path| path.file_name())
+                                .map(|name| name.to_string_lossy().into_owned())
+                            {
+                                app.current_screen =
+                                    match app.run_script(&vm_name, &name, String::new()) {
+                                        Ok(()) => Screen::Script { vm_name },
+                                        Err(error) => Screen::ScriptFailed { vm_name, error },
+                                    }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::Script { ref vm_name } => {
+                    let vm_name = vm_name.clone();
+                    match key_event.code {
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Char('x') => {
+                            if let Some(run) = app.scripts.get(&vm_name) {
+                                run.abort();
+                            }
+                        }
+                        KeyCode::Char('r') => {
+                            // Runs the same script again once it has ended
+                            if let Some(run) = app.scripts.get(&vm_name)
+                                && run.result.is_some()
+                            {
+                                let name = run.name.clone();
+                                if let Err(error) = app.run_script(&vm_name, &name, String::new()) {
+                                    app.current_screen = Screen::ScriptFailed { vm_name, error };
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::Player => {
+                    if let Some(player) = app.player.as_mut() {
+                        match key_event.code {
+                            KeyCode::Esc | KeyCode::Char('q') => {
+                                app.player = None;
+                                app.current_screen = Screen::Recordings { selected: 0 };
+                            }
+                            KeyCode::Char(' ') => player.toggle_pause(),
+                            KeyCode::Char('+') => player.faster(),
+                            KeyCode::Char('-') => player.slower(),
+                            KeyCode::Left => player.seek(-SEEK_SECONDS),
+                            KeyCode::Right => player.seek(SEEK_SECONDS),
+                            KeyCode::Home => player.seek(-player.duration),
+                            _ => {}
+                        }
+                    }
+                }
+                Screen::AttachDisk { selected } => {
+                    let images = app.image_names();
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Up => {
+                            app.current_screen = Screen::AttachDisk {
+                                selected: selected.saturating_sub(1),
+                            }
+                        }
+                        KeyCode::Down => {
+                            app.current_screen = Screen::AttachDisk {
+                                selected: (selected + 1).min(images.len().saturating_sub(1)),
+                            }
+                        }
+                        KeyCode::Enter => {
+                            app.current_screen = Screen::List;
+                            if let Some(image) = images.get(selected)
+                                && let Err(error) = app.attach_image_to_selected_vm(image)
+                            {
+                                app.current_screen = Screen::QmpFailed {
+                                    vm_name: app
+                                        .selected_vm()
+                                        .map(|vm| vm.name.clone())
+                                        .unwrap_or_default(),
+                                    error,
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::DetachDisk { selected } => {
+                    let disks = app
+                        .selected_vm()
+                        .map(|vm| vm.attached_disks.clone())
+                        .unwrap_or_default();
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Up => {
+                            app.current_screen = Screen::DetachDisk {
+                                selected: selected.saturating_sub(1),
+                            }
+                        }
+                        KeyCode::Down => {
+                            app.current_screen = Screen::DetachDisk {
+                                selected: (selected + 1).min(disks.len().saturating_sub(1)),
+                            }
+                        }
+                        KeyCode::Enter => {
+                            app.current_screen = Screen::List;
+                            if let Some(disk) = disks.into_iter().nth(selected) {
+                                app.detach_disk_from_selected_vm(disk.id);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::Balloon { ref target } => {
+                    let mut target = target.clone();
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Char(c) if c.is_ascii_alphanumeric() => {
+                            target.push(c);
+                            app.current_screen = Screen::Balloon { target };
+                        }
+                        KeyCode::Backspace => {
+                            target.pop();
+                            app.current_screen = Screen::Balloon { target };
+                        }
+                        KeyCode::Enter => {
+                            match vm::helpers::parse_mem(&target)
+                                .and_then(|target| app.set_balloon_of_selected_vm(target))
+                            {
+                                Ok(()) => app.current_screen = Screen::List,
+                                Err(error) => {
+                                    app.current_screen = Screen::QmpFailed {
+                                        vm_name: app
+                                            .selected_vm()
+                                            .map(|vm| vm.name.clone())
+                                            .unwrap_or_default(),
+                                        error,
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Screen::SendKeys {
+                    selected,
+                    hold_time,
+                    ref custom,
+                } => {
+                    let mut custom = custom.clone();
+                    // The last entry is the custom key sequence
+                    let custom_selected = selected == KEY_COMBINATIONS.len();
+                    match key_event.code {
+                        KeyCode::Esc => {
+                            app.current_screen = Screen::List;
+                        }
+                        KeyCode::Up => {
+                            app.current_screen = Screen::SendKeys {
+                                selected: selected.saturating_sub(1),
+                                hold_time,
+                                custom,
+                            }
+                        }
+                        KeyCode::Down => {
+                            app.current_screen = Screen::SendKeys {
+                                selected: (selected + 1).min(KEY_COMBINATIONS.len()),
+                                hold_time,
+                                custom,
+                            }
+                        }
+                        KeyCode::Tab => {
+                            app.current_screen = Screen::SendKeys {
+                                selected,
+                                hold_time: (hold_time + 1) % HOLD_TIMES_MS.len(),
+                                custom,
+                            }
+                        }
+                        KeyCode::Char(c) if custom_selected => {
+                            custom.push(c);
+                            app.current_screen = Screen::SendKeys {
+                                selected,
+                                hold_time,
+                                custom,
+                            }
+                        }
+                        KeyCode::Backspace if custom_selected => {
+                            custom.pop();
+                            app.current_screen = Screen::SendKeys {
+                                selected,
+                                hold_time,
+                                custom,
+                            }
+                        }
+                        KeyCode::Enter => {
+                            let keys = if custom_selected {
+                                qmp::parse_key_sequence(&custom)
+                            } else {
+                                Ok(vec![
+                                    KEY_COMBINATIONS[selected]
+                                        .1
+                                        .iter()
+                                        .map(|key| key.to_string())
+                                        .collect(),
+                                ])
+                            };
+                            match keys {
+                                Ok(keys) => {
+                                    app.send_keys_to_selected_vm(keys, HOLD_TIMES_MS[hold_time]);
+                                    app.current_screen = Screen::List;
+                                }
+                                Err(error) => {
+                                    app.current_screen = Screen::QmpFailed {
+                                        vm_name: app
+                                            .selected_vm()
+                                            .map(|vm| vm.name.clone())
+                                            .unwrap_or_default(),
+                                        error,
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
             }
         }
 
         AppEvent::Key(_) => {}
 
         AppEvent::StartNbSuccess { vm_name } => {
+            app.activity.info(&vm_name, "startnb.sh succeeded");
             let base_dir = app.base_dir.clone();
             if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
                 vm.update_state(&base_dir);
             };
         }
 
+        AppEvent::StartNbOutput { vm_name, line } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.start_log.push(line);
+            }
+        }
+
         AppEvent::StartNbFailed {
             vm_name,
             error,
             stdout,
             stderr,
         } => {
+            app.activity.error(&vm_name, &error);
+            let base_dir = app.base_dir.clone();
             if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                // Keep the saved state for the next try if the VM was resuming
+                let _ = std::fs::rename(
+                    vm.resuming_state_path(&base_dir),
+                    vm.saved_state_path(&base_dir),
+                );
                 vm.state = VmState::Stopped;
+                vm.boot_started = None;
+                vm.update_state(&base_dir);
             }
             app.current_screen = Screen::StartNbFailed {
                 vm_name,
@@ -89,18 +924,187 @@ pub fn handle(app: &mut State, event: AppEvent) -> Result<(), Box<dyn std::error
 
         AppEvent::ForceRender => {}
 
+        AppEvent::Tick => {
+            app.sample_vms();
+            app.refresh_modified_images();
+            app.rotate_logs();
+            app.update_serial_loggers();
+            if let Some(log_view) = app.log_view.as_mut() {
+                log_view.reload();
+            }
+        }
+
+        AppEvent::BlockStatsSampled {
+            vm_name,
+            time,
+            stats,
+        } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.blockstats_pending = false;
+                match stats {
+                    Ok(stats) if vm.is_running() => vm.update_disks_io(time, stats),
+                    // The VM has been stopped or QMP isn't available (yet?)
+                    _ => vm.clear_disks_io(),
+                }
+            }
+        }
+
         AppEvent::KillFailed { vm_name, error } => {
+            app.activity.error(&vm_name, &error);
             app.current_screen = Screen::KillFailed { vm_name, error }
         }
-        AppEvent::FatalError(err) => app.fatal_error = Some(err),
+        AppEvent::BalloonQueried { vm_name, actual } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.balloon = Some(actual);
+            }
+        }
+
+        AppEvent::DiskAttached { vm_name, disk } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.attached_disks.push(disk);
+            }
+        }
+
+        AppEvent::DiskDetached { vm_name, id } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.attached_disks.retain(|disk| disk.id != id);
+            }
+        }
+
+        AppEvent::PortForwardsListed {
+            vm_name,
+            netdev,
+            forwards,
+        } => {
+            if let Screen::PortForwards {
+                selected, input, ..
+            } = &app.current_screen
+                && app.selected_vm().is_some_and(|vm| vm.name == vm_name)
+            {
+                app.current_screen = Screen::PortForwards {
+                    selected: (*selected).min(forwards.len().saturating_sub(1)),
+                    input: input.clone(),
+                    netdev,
+                    forwards,
+                }
+            }
+        }
+
+        AppEvent::AgentQueried { vm_name, info } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.agent_pending = false;
+                match info {
+                    Ok(info) if vm.is_running() => {
+                        vm.agent_available = Some(true);
+                        vm.guest_info = Some(info);
+                    }
+                    Ok(_) => {}
+                    Err(_) => {
+                        vm.agent_available = Some(false);
+                        vm.guest_info = None;
+                    }
+                }
+            }
+        }
+
+        AppEvent::AttachedDisksQueried { vm_name, disks } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                match disks {
+                    Ok(disks) if vm.is_running() => vm.attached_disks = disks,
+                    // QMP may not be available yet, the disks are queried again at the next tick
+                    _ => vm.attached_disks_queried = false,
+                }
+            }
+        }
+
+        AppEvent::VcpuThreadsQueried { vm_name, threads } => {
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.vcpu_threads_pending = false;
+                if let Ok(threads) = threads
+                    && vm.is_running()
+                {
+                    vm.vcpu_threads = threads;
+                }
+            }
+        }
+
+        AppEvent::QmpFailed { vm_name, error } => {
+            app.activity.error(&vm_name, &error);
+            // The hibernation or the guest shutdown may have failed
+            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+                vm.expected_exit = None;
+            }
+            app.current_screen = Screen::QmpFailed { vm_name, error }
+        }
+        AppEvent::ConsoleOutput { vm_name, data } => {
+            if let Some(console) = app.console.as_mut()
+                && console.vm_name == vm_name
+            {
+                console.process(&data);
+            }
+        }
+        AppEvent::ConsoleClosed { vm_name, error } => {
+            if let Some(error) = &error {
+                app.activity
+                    .warning(&vm_name, &format!("Serial console closed: {error}"));
+            }
+            if let Some(console) = app.console.as_mut()
+                && console.vm_name == vm_name
+            {
+                console
+                    .closed
+                    .get_or_insert(error.unwrap_or("connection closed by QEMU".to_owned()));
+            }
+        }
+        AppEvent::ScriptProgress {
+            vm_name,
+            step,
+            status,
+        } => {
+            if let Some(run) = app.scripts.get_mut(&vm_name)
+                && let Some((_, step_status)) = run.steps.get_mut(step)
+            {
+                *step_status = status;
+            }
+        }
+        AppEvent::ScriptFinished { vm_name, result } => {
+            if let Some(run) = app.scripts.get_mut(&vm_name) {
+                match &result {
+                    Ok(()) => app
+                        .activity
+                        .info(&vm_name, &format!("Script '{}' passed", run.name)),
+                    Err(err) => app
+                        .activity
+                        .error(&vm_name, &format!("Script '{}' failed: {err}", run.name)),
+                }
+                run.result = Some(result);
+            }
+        }
+        AppEvent::PlayerTick => {
+            if let Some(player) = app.player.as_mut() {
+                player.advance();
+            }
+        }
+        AppEvent::FatalError(err) => {
+            app.activity.record(Severity::Error, None, &err);
+            app.fatal_error = Some(err);
+        }
 
         AppEvent::VmConfCreated(filename) => {
+            app.activity
+                .info(conf_vm_name(&filename), "Configuration created");
             app.add_vm(&filename);
         }
 
-        AppEvent::VmConfModified(filename) => {}
+        AppEvent::VmConfModified(filename) => {
+            app.activity
+                .info(conf_vm_name(&filename), "Configuration modified");
+            app.reload_vm(&filename);
+        }
 
         AppEvent::VmConfDeleted(filename) => {
+            app.activity
+                .info(conf_vm_name(&filename), "Configuration deleted");
             app.delete_vm(&filename);
         }
 
@@ -111,29 +1115,113 @@ pub fn handle(app: &mut State, event: AppEvent) -> Result<(), Box<dyn std::error
         AppEvent::KernelDeleted(filename) => {}
 
         AppEvent::PidFileDeleted(vm_name) => {
-            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+            let base_dir = app.base_dir.clone();
+            if let Some(vm) = app.vms.iter_mut().find(|vm| vm.name == vm_name) {
+                // QEMU is expected to exit only when the VM has been stopped, hibernated
+                // or shut down from the TUI
+                match (&vm.state, &vm.expected_exit) {
+                    (VmState::Stopping | VmState::StoppingToDelete, _) => app
+                        .activity
+                        .info(&vm_name, "QEMU exited (PID file deleted)"),
+                    (_, Some(reason)) => app.activity.info(
+                        &vm_name,
+                        &format!("QEMU exited after the {reason} (PID file deleted)"),
+                    ),
+                    _ => app
+                        .activity
+                        .warning(&vm_name, "QEMU exited unexpectedly (PID file deleted)"),
+                }
+                // QEMU has exited, so the state it was resumed from isn't needed anymore
+                let _ = std::fs::remove_file(vm.resuming_state_path(&base_dir));
                 match vm.state {
                     VmState::StoppingToDelete => {
                         app.vms.retain(|item| item.name != vm_name);
                     }
-                    _ => vm.state = VmState::Stopped,
+                    _ => {
+                        vm.set_stopped();
+                        // The VM may have been hibernated
+                        vm.update_state(&base_dir);
+                    }
                 }
             }
         }
 
         AppEvent::PidFileCreated(vm_name) => {
             let base_dir = app.base_dir.clone();
-            if let Some(vm) = app.get_mut_vm_by_name(&vm_name) {
+            if let Some(vm) = app.vms.iter_mut().find(|vm| vm.name == vm_name) {
                 vm.set_pid(&base_dir);
+                app.activity.info(
+                    &vm_name,
+                    &match vm.pid() {
+                        Some(pid) => format!("QEMU started (PID {pid})"),
+                        None => "PID file created".to_owned(),
+                    },
+                );
             }
         }
 
-        AppEvent::ImageFileCreated(filename) => {}
+        AppEvent::ImageFileCreated(_) | AppEvent::ImageFileDeleted(_) => app.refresh_images(),
 
-        AppEvent::ImageFileModified(filename) => {}
+        // Images are modified by each write of the VMs, their usage is refreshed at the next tick
+        AppEvent::ImageFileModified(filename) => {
+            app.modified_images.insert(filename);
+        }
+
+        AppEvent::SocketCreated(filename) => {
+            // The appearance of the QMP socket means that QEMU is running
+            let base_dir = app.base_dir.clone();
+            for vm in app
+                .vms
+                .iter_mut()
+                .filter(|vm| vm.qmp_socket.as_ref() == Some(&filename))
+            {
+                vm.update_state(&base_dir);
+            }
+        }
 
-        AppEvent::ImageFileDeleted(filename) => {}
+        AppEvent::OtherFileDeleted(filename) => {
+            // The disappearance of the QMP socket means that QEMU has exited
+            for vm in app
+                .vms
+                .iter_mut()
+                .filter(|vm| vm.qmp_socket.as_ref() == Some(&filename))
+            {
+                vm.qmp_socket_present = false;
+                if vm.is_running() && !vm.is_process_alive() {
+                    vm.set_stopped();
+                }
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Handles a key typed while a search pattern is being typed in `input`.
+/// Returns true when the pattern has been submitted (with Enter).
+fn edit_search_input(input: &mut Option<String>, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Esc => *input = None,
+        KeyCode::Enter => return true,
+        KeyCode::Backspace => {
+            if let Some(input) = input {
+                input.pop();
+            }
+        }
+        KeyCode::Char(c) => {
+            if let Some(input) = input {
+                input.push(c);
+            }
+        }
+        _ => {}
+    }
+    false
+}
+
+/// Name of the VM of a configuration file (`{base_dir}etc/<name>.conf`)
+fn conf_vm_name(conf_file: &str) -> &str {
+    Path::new(conf_file)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or(conf_file)
+}
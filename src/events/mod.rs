@@ -1,9 +1,11 @@
 mod fs;
 mod handle;
 mod term;
+mod tick;
 mod types;
 
 pub use fs::get_fs_events;
 pub use handle::handle;
-pub use term::get_term_events;
+pub use term::{TermEventsPause, get_term_events};
+pub use tick::get_tick_events;
 pub use types::AppEvent;
@@ -1,11 +1,51 @@
 use ratatui::crossterm;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
+use std::time::Duration;
 
 use crate::events::AppEvent;
 
+/// How long the terminal events thread waits for an event before checking if it must pause
+const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Allows the main thread to stop `get_term_events()` from reading the terminal,
+/// ie. while stdin is connected to the raw console of a VM
+#[derive(Clone, Default)]
+pub struct TermEventsPause {
+    requested: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+}
+
+impl TermEventsPause {
+    /// Returns once the terminal events thread doesn't read the terminal anymore
+    pub fn pause(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        while !self.paused.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn resume(&self) {
+        self.requested.store(false, Ordering::SeqCst);
+    }
+}
+
 /// This function listens for events from the terminal and send the relevant ones as `AppEvent`
-pub fn get_term_events(tx: Sender<AppEvent>) {
+pub fn get_term_events(tx: Sender<AppEvent>, pause: TermEventsPause) {
     loop {
+        if pause.requested.load(Ordering::SeqCst) {
+            pause.paused.store(true, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+        pause.paused.store(false, Ordering::SeqCst);
+        // A pause may have been requested before `paused` was reset
+        if pause.requested.load(Ordering::SeqCst) || !crossterm::event::poll(POLL_TIMEOUT).unwrap()
+        {
+            continue;
+        }
+
         let event = crossterm::event::read().unwrap();
         match event {
             crossterm::event::Event::FocusGained => (),
@@ -0,0 +1,12 @@
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use crate::events::AppEvent;
+
+/// This function sends an `AppEvent::Tick` every `interval`, used to sample the VMs statistics
+pub fn get_tick_events(tx: Sender<AppEvent>, interval: Duration) {
+    loop {
+        std::thread::sleep(interval);
+        tx.send(AppEvent::Tick).unwrap();
+    }
+}
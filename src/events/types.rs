@@ -1,8 +1,17 @@
 use ratatui::crossterm::event::KeyEvent;
+use std::time::Instant;
+
+use crate::console::script::StepStatus;
+use crate::vm::{
+    AttachedDisk, OutputLine,
+    qga::GuestInfo,
+    qmp::{BlockStats, PortForward},
+};
 
 #[derive(Debug)]
 pub enum AppEvent {
     ForceRender,
+    Tick,
     Key(KeyEvent),
     StartNbFailed {
         vm_name: String,
@@ -13,10 +22,76 @@ pub enum AppEvent {
     StartNbSuccess {
         vm_name: String,
     },
+    /// A line written by startnb.sh while it starts a VM
+    StartNbOutput {
+        vm_name: String,
+        line: OutputLine,
+    },
     KillFailed {
         vm_name: String,
         error: String,
     },
+    BalloonQueried {
+        vm_name: String,
+        actual: u64,
+    },
+    DiskAttached {
+        vm_name: String,
+        disk: AttachedDisk,
+    },
+    DiskDetached {
+        vm_name: String,
+        id: String,
+    },
+    BlockStatsSampled {
+        vm_name: String,
+        time: Instant,
+        stats: Result<Vec<BlockStats>, String>,
+    },
+    PortForwardsListed {
+        vm_name: String,
+        netdev: Option<String>,
+        forwards: Vec<PortForward>,
+    },
+    AgentQueried {
+        vm_name: String,
+        info: Result<GuestInfo, String>,
+    },
+    AttachedDisksQueried {
+        vm_name: String,
+        disks: Result<Vec<AttachedDisk>, String>,
+    },
+    VcpuThreadsQueried {
+        vm_name: String,
+        threads: Result<Vec<u32>, String>,
+    },
+    QmpFailed {
+        vm_name: String,
+        error: String,
+    },
+    /// Output of the serial console of a VM
+    ConsoleOutput {
+        vm_name: String,
+        data: Vec<u8>,
+    },
+    /// The serial console of a VM has been closed, `error` is set if it wasn't a clean end of file
+    ConsoleClosed {
+        vm_name: String,
+        error: Option<String>,
+    },
+    /// Time to play the next frame of the console recording
+    PlayerTick,
+    /// A step of the console script running on a VM has a new status
+    ScriptProgress {
+        vm_name: String,
+        step: usize,
+        status: StepStatus,
+    },
+    /// The console script running on a VM has ended, `result` is the error if it failed
+    ScriptFinished {
+        vm_name: String,
+        result: Result<(), String>,
+    },
     FatalError(String),
     VmConfCreated(String),
     VmConfModified(String),
@@ -29,4 +104,9 @@ pub enum AppEvent {
     ImageFileCreated(String),
     ImageFileModified(String),
     ImageFileDeleted(String),
+    /// A Unix socket has been created (path relative to the base directory)
+    SocketCreated(String),
+    /// A file which isn't a VM configuration, an image, a kernel or a PID file has been deleted
+    /// (path relative to the base directory)
+    OtherFileDeleted(String),
 }
@@ -1,9 +1,11 @@
 mod app;
+mod console;
 mod events;
 mod ui;
 mod vm;
 
-use crate::events::{AppEvent, get_fs_events, get_term_events};
+use crate::events::{AppEvent, TermEventsPause, get_fs_events, get_term_events, get_tick_events};
+use crate::ui::Screen;
 use std::sync::mpsc;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -13,12 +15,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Start a thread to listen to crossterm events
     let tx_clone = tx.clone();
-    let term_thread = std::thread::spawn(|| get_term_events(tx));
+    let tx_tick = tx.clone();
+    let term_events_pause = TermEventsPause::default();
+    let term_events_pause_clone = term_events_pause.clone();
+    let term_thread = std::thread::spawn(|| get_term_events(tx, term_events_pause_clone));
 
     // Start a thread to handle FS events
     let base_dir_clone = app.base_dir.clone();
     let fs_notify_thread = std::thread::spawn(|| get_fs_events(tx_clone, base_dir_clone));
 
+    // Start a thread to periodically sample the VMs statistics
+    let sample_interval = app.config.sample_interval;
+    let tick_thread = std::thread::spawn(move || get_tick_events(tx_tick, sample_interval));
+
     ratatui::run(|terminal| {
         while !app.exit {
             terminal.draw(|frame| ui::render(frame, &mut app)).unwrap();
@@ -32,6 +41,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 break;
             }
 
+            if let Some((vm_name, path, log)) = app.raw_console.take() {
+                // The terminal events thread must not swallow the keys typed in the console
+                term_events_pause.pause();
+                let res = console::raw::attach(
+                    &vm_name,
+                    &path,
+                    log.as_deref(),
+                    &app.config.console_escape_bytes,
+                    &app.config.console_escape,
+                );
+                term_events_pause.resume();
+                terminal.clear().unwrap();
+                if let Err(reason) = res {
+                    app.current_screen = Screen::ConsoleUnavailable { vm_name, reason };
+                }
+            }
+
             if term_thread.is_finished() {
                 app.fatal_error
                     .get_or_insert("term_thread has finished!".to_owned());
@@ -43,6 +69,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .get_or_insert("fs_notify_thread has finished!".to_owned());
                 break;
             }
+
+            if tick_thread.is_finished() {
+                app.fatal_error
+                    .get_or_insert("tick_thread has finished!".to_owned());
+                break;
+            }
         }
     });
 
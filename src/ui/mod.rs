@@ -15,7 +15,13 @@ pub const INVALID_CONF_VM_FG: Color = Color::Red;
 pub const STARTING_VM_FG: Color = Color::LightGreen;
 pub const STOPPING_VM_FG: Color = Color::Magenta;
 const ACTION_COLOR: Color = Color::Magenta;
+const SEARCH_MATCH_BG: Color = Color::Indexed(244);
+const CURRENT_SEARCH_MATCH_BG: Color = Color::Yellow;
 const DEFAULT_SPACING_PADDING: u16 = 1;
+/// Minimum number of lines of the details pane when the resource usage history is displayed next to it
+const HISTORY_PANE_HEIGHT: usize = 9;
+/// Number of lines of the startnb.sh output shown below the VMs list while a VM is starting
+const START_LOG_PANE_HEIGHT: u16 = 8;
 pub const LOGO: &[u8; 16255] = include_bytes!("../../assets/smolBSD.png");
 
 pub use render::render;
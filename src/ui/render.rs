@@ -1,36 +1,50 @@
 use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Flex, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::{Color, Modifier, Style, Stylize},
     text::{Line, Span, Text},
-    widgets::{Block, BorderType::Rounded, Borders, Clear, Padding, Paragraph, Row, Table, Wrap},
+    widgets::{
+        Block, BorderType::Rounded, Borders, Cell, Clear, Padding, Paragraph, Row, Sparkline,
+        Table, Wrap,
+    },
 };
 use ratatui_image::StatefulImage;
 
 use crate::{
-    app::{State, VERSION},
+    app::{State, VERSION, activity::Severity},
+    console::{
+        self,
+        player::SPEEDS,
+        script::{SCRIPTS_DIR, StepStatus},
+    },
     ui::{
-        ACTION_COLOR, DEFAULT_SPACING_PADDING, INFO_COLOR, POPUP_BORDER_COLOR,
-        SELECTED_BUTTON_BG_COLOR, SELECTED_BUTTON_FG_COLOR, Screen, UNSELECTED_BUTTON_BG_COLOR,
-        UNSELECTED_BUTTON_FG_COLOR,
+        ACTION_COLOR, CURRENT_SEARCH_MATCH_BG, DEFAULT_SPACING_PADDING, HISTORY_PANE_HEIGHT,
+        INFO_COLOR, INVALID_CONF_VM_FG, POPUP_BORDER_COLOR, RUNNING_VM_FG, SEARCH_MATCH_BG,
+        SELECTED_BUTTON_BG_COLOR, SELECTED_BUTTON_FG_COLOR, START_LOG_PANE_HEIGHT, STARTING_VM_FG,
+        STOPPED_VM_FG, Screen, UNSELECTED_BUTTON_BG_COLOR, UNSELECTED_BUTTON_FG_COLOR,
+    },
+    vm::{
+        self, Vm, VmState,
+        helpers::{format_bytes, format_duration},
+        history::Sample,
+        image::ImageUsage,
+        qmp::{HOLD_TIMES_MS, KEY_COMBINATIONS},
     },
 };
 
 pub fn render(frame: &mut Frame, app: &mut State) {
     let screen = app.current_screen.clone();
 
-    let [header_chunk, vms_list_chunk] =
+    let [header_chunk, main_chunk] =
         Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(frame.area());
 
     match screen {
         Screen::List => {
-            render_header(frame, app, header_chunk);
-            render_vms_list(frame, app, vms_list_chunk);
+            render_main(frame, app, header_chunk, main_chunk);
         }
 
         Screen::DeleteConfirmation(ok) => {
-            render_header(frame, app, header_chunk);
-            render_vms_list(frame, app, vms_list_chunk);
+            render_main(frame, app, header_chunk, main_chunk);
             if let Some(current_vm) = app.vms.get(app.table_state.selected().unwrap()) {
                 render_popup(
                     frame,
@@ -45,14 +59,76 @@ pub fn render(frame: &mut Frame, app: &mut State) {
             }
         }
 
+        Screen::Help => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            let key_width = KEY_HINTS
+                .iter()
+                .map(|(key, _)| key.len())
+                .max()
+                .unwrap_or(0);
+            let lines: Vec<_> = KEY_HINTS
+                .iter()
+                .map(|(key, action)| {
+                    Line::from(vec![
+                        format!("  {key:>key_width$}").fg(ACTION_COLOR),
+                        format!(" {action}  ").into(),
+                    ])
+                })
+                .collect();
+            render_popup(frame, " Keys ", Paragraph::new(lines), None);
+        }
+
+        Screen::ShutdownConfirmation(ok) => {
+            render_main(frame, app, header_chunk, main_chunk);
+            if let Some(current_vm) = app.selected_vm() {
+                render_popup(
+                    frame,
+                    " Guest shutdown ",
+                    Paragraph::new(format!(
+                        "Are you sure you want to shut VM '{}' down",
+                        current_vm.name
+                    ))
+                    .centered(),
+                    Some(ok),
+                );
+            }
+        }
+
+        Screen::OvercommitConfirmation {
+            vm_name,
+            committed,
+            total,
+            ok,
+        } => {
+            render_main(frame, app, header_chunk, main_chunk);
+            render_popup(
+                frame,
+                " ⚠️ Memory overcommit ⚠️ ",
+                Paragraph::new(vec![
+                    Line::from(format!(
+                        "Starting VM '{vm_name}' would commit {} of memory to the VMs,",
+                        format_bytes(committed)
+                    )),
+                    Line::from(format!(
+                        "more than {} times the {} of the host.",
+                        app.config.mem_overcommit_ratio,
+                        format_bytes(total)
+                    )),
+                    Line::from("Start it anyway?"),
+                ])
+                .centered(),
+                Some(ok),
+            );
+        }
+
         Screen::StartNbFailed {
             vm_name,
             error,
             stdout,
             stderr,
         } => {
-            render_header(frame, app, header_chunk);
-            render_vms_list(frame, app, vms_list_chunk);
+            render_main(frame, app, header_chunk, main_chunk);
 
             let mut lines = vec![
                 Line::from(error).centered(),
@@ -74,8 +150,7 @@ pub fn render(frame: &mut Frame, app: &mut State) {
         }
 
         Screen::KillFailed { vm_name, error } => {
-            render_header(frame, app, header_chunk);
-            render_vms_list(frame, app, vms_list_chunk);
+            render_main(frame, app, header_chunk, main_chunk);
 
             let lines = vec![Line::from(error).centered()];
 
@@ -86,10 +161,641 @@ pub fn render(frame: &mut Frame, app: &mut State) {
                 None,
             );
         }
+
+        Screen::SendKeys {
+            selected,
+            hold_time,
+            custom,
+        } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            let mut lines: Vec<Line> = KEY_COMBINATIONS
+                .iter()
+                .map(|(label, _)| Line::from(format!("  {label}")))
+                .collect();
+            lines.push(Line::from(format!(
+                "  Custom sequence (ie. ctrl-alt-f2 ret): {custom}_"
+            )));
+            if let Some(line) = lines.get_mut(selected) {
+                *line = line.clone().reversed();
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec![
+                "Hold time: ".fg(INFO_COLOR),
+                format!("{}ms ", HOLD_TIMES_MS[hold_time]).into(),
+                "<Tab>".fg(ACTION_COLOR),
+                " to change".into(),
+            ]));
+
+            render_popup(
+                frame,
+                &format!(
+                    " Send keys to VM '{}' ",
+                    app.selected_vm().map(|vm| vm.name.as_str()).unwrap_or("")
+                ),
+                Paragraph::new(lines),
+                None,
+            );
+        }
+
+        Screen::QmpPortIssues {
+            issues,
+            free_port,
+            ok,
+        } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            if let Some(current_vm) = app.selected_vm() {
+                let mut lines: Vec<Line> = if issues.is_empty() {
+                    vec![Line::from("No QMP port issue found").centered()]
+                } else {
+                    issues
+                        .into_iter()
+                        .map(|issue| Line::from(format!("⚠️ {issue}")))
+                        .collect()
+                };
+                if let Some(free_port) = free_port {
+                    lines.push(Line::from(""));
+                    lines.push(
+                        Line::from(format!(
+                            "Use the free port {free_port} and save it in etc/{}.conf?",
+                            current_vm.name
+                        ))
+                        .centered(),
+                    );
+                }
+
+                render_popup(
+                    frame,
+                    &format!(" QMP port of VM '{}' ", current_vm.name),
+                    Paragraph::new(lines),
+                    free_port.map(|_| ok),
+                );
+            }
+        }
+
+        Screen::PortForwards {
+            forwards,
+            selected,
+            input,
+            ..
+        } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            if let Some(current_vm) = app.selected_vm() {
+                let mut lines = selectable_lines(
+                    forwards
+                        .iter()
+                        .map(|forward| {
+                            format!(
+                                "{} {}:{} -> {}:{}",
+                                forward.protocol.to_uppercase(),
+                                match forward.host_addr.as_str() {
+                                    "" => "*",
+                                    host_addr => host_addr,
+                                },
+                                forward.host_port,
+                                forward.guest_addr,
+                                forward.guest_port
+                            )
+                        })
+                        .collect(),
+                    selected,
+                    "No port forward",
+                );
+                lines.push(Line::from(""));
+                match input {
+                    Some(input) => {
+                        lines.push(Line::from(vec![
+                            "New port forward: ".fg(INFO_COLOR),
+                            format!("{input}_").into(),
+                        ]));
+                        lines.push(
+                            Line::from("(ie. tcp::8080-:80 or tcp:127.0.0.1:2222-:22)")
+                                .fg(INFO_COLOR),
+                        );
+                    }
+                    None => lines.push(Line::from(vec![
+                        "<a>".fg(ACTION_COLOR),
+                        " Add  ".into(),
+                        "<d>".fg(ACTION_COLOR),
+                        " Remove  ".into(),
+                        "<w>".fg(ACTION_COLOR),
+                        format!(" Save in etc/{}.conf", current_vm.name).into(),
+                    ])),
+                }
+
+                render_popup(
+                    frame,
+                    &format!(" Port forwards of VM '{}' ", current_vm.name),
+                    Paragraph::new(lines),
+                    None,
+                );
+            }
+        }
+
+        Screen::Images { selected } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            let mut lines = selectable_lines(
+                app.image_names()
+                    .into_iter()
+                    .map(|image| {
+                        let users: Vec<&str> = app
+                            .vms
+                            .iter()
+                            .filter(|vm| vm.uses_image(&image))
+                            .map(|vm| vm.name.as_str())
+                            .collect();
+                        format!(
+                            "{image}: {}{}",
+                            app.image_usages
+                                .get(&image)
+                                .map(ImageUsage::summary)
+                                .unwrap_or("?".to_owned()),
+                            if users.is_empty() {
+                                String::new()
+                            } else {
+                                format!(", used by {}", users.join(", "))
+                            }
+                        )
+                    })
+                    .collect(),
+                selected,
+                "There is no image in images/",
+            );
+            let (apparent, allocated) = app.images_total();
+            lines.push(Line::from(""));
+            lines.push(
+                Line::from(vec![
+                    "Total: ".fg(INFO_COLOR),
+                    format!(
+                        "{} ({} allocated)",
+                        format_bytes(apparent),
+                        format_bytes(allocated)
+                    )
+                    .into(),
+                ])
+                .centered(),
+            );
+
+            render_popup(frame, " Images ", Paragraph::new(lines), None);
+        }
+
+        Screen::SavedStates { selected } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            let mut lines = selectable_lines(
+                app.saved_states()
+                    .iter()
+                    .map(|path| {
+                        format!(
+                            "{} ({})",
+                            path.file_stem().unwrap_or_default().to_string_lossy(),
+                            std::fs::metadata(path)
+                                .map(|metadata| format_bytes(metadata.len()))
+                                .unwrap_or("?".to_owned())
+                        )
+                    })
+                    .collect(),
+                selected,
+                "No hibernated VM",
+            );
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec!["<d>".fg(ACTION_COLOR), " Delete".into()]).centered());
+
+            render_popup(frame, " Saved states ", Paragraph::new(lines), None);
+        }
+
+        Screen::Recordings { selected } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            let mut lines = selectable_lines(
+                console::record::recording_paths(&app.base_dir)
+                    .iter()
+                    .map(|path| {
+                        format!(
+                            "{} ({})",
+                            path.file_stem().unwrap_or_default().to_string_lossy(),
+                            std::fs::metadata(path)
+                                .map(|metadata| format_bytes(metadata.len()))
+                                .unwrap_or("?".to_owned())
+                        )
+                    })
+                    .collect(),
+                selected,
+                "No console recording",
+            );
+            lines.push(Line::from(""));
+            lines.push(
+                Line::from(vec![
+                    "<Enter>".fg(ACTION_COLOR),
+                    " Play ".into(),
+                    "<d>".fg(ACTION_COLOR),
+                    " Delete".into(),
+                ])
+                .centered(),
+            );
+
+            render_popup(frame, " Console recordings ", Paragraph::new(lines), None);
+        }
+
+        Screen::Scripts { selected } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            let vm_name = app
+                .selected_vm()
+                .map(|vm| vm.name.clone())
+                .unwrap_or_default();
+            let mut lines = selectable_lines(
+                console::script::script_paths(&app.base_dir, &vm_name)
+                    .iter()
+                    .map(|path| {
+                        path.file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned()
+                    })
+                    .collect(),
+                selected,
+                &format!("No script in {SCRIPTS_DIR}/{vm_name}/"),
+            );
+            lines.push(Line::from(""));
+            lines.push(Line::from(vec!["<Enter>".fg(ACTION_COLOR), " Run".into()]).centered());
+
+            render_popup(
+                frame,
+                &format!(" Console scripts of '{vm_name}' "),
+                Paragraph::new(lines),
+                None,
+            );
+        }
+
+        Screen::Script { vm_name } => {
+            render_header(frame, app, header_chunk);
+            render_script(frame, app, &vm_name, main_chunk);
+        }
+
+        Screen::ScriptFailed { vm_name, error } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            render_popup(
+                frame,
+                &format!(" ❌ Failed to run a script on VM '{}' ❌ ", vm_name),
+                Paragraph::new(vec![Line::from(error).centered()]),
+                None,
+            );
+        }
+
+        Screen::Player => {
+            render_header(frame, app, header_chunk);
+            render_player(frame, app, main_chunk);
+        }
+
+        Screen::RecordingFailed { error } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            render_popup(
+                frame,
+                " ❌ Console recording ❌ ",
+                Paragraph::new(vec![Line::from(error).centered()]),
+                None,
+            );
+        }
+
+        Screen::AttachDisk { selected } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            let lines = selectable_lines(
+                app.image_names()
+                    .into_iter()
+                    .map(|image| match app.image_usages.get(&image) {
+                        Some(usage) => format!("{image}: {}", usage.summary()),
+                        None => image,
+                    })
+                    .collect(),
+                selected,
+                "There is no image in images/",
+            );
+            render_popup(
+                frame,
+                &format!(
+                    " Attach an image to VM '{}' ",
+                    app.selected_vm().map(|vm| vm.name.as_str()).unwrap_or("")
+                ),
+                Paragraph::new(lines),
+                None,
+            );
+        }
+
+        Screen::DetachDisk { selected } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            if let Some(current_vm) = app.selected_vm() {
+                let lines = selectable_lines(
+                    current_vm
+                        .attached_disks
+                        .iter()
+                        .map(|disk| format!("{} ({})", disk.image, disk.id))
+                        .collect(),
+                    selected,
+                    "No disk has been attached to this VM",
+                );
+                render_popup(
+                    frame,
+                    &format!(" Detach a disk from VM '{}' ", current_vm.name),
+                    Paragraph::new(lines),
+                    None,
+                );
+            }
+        }
+
+        Screen::Balloon { target } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            if let Some(current_vm) = app.selected_vm() {
+                let lines = vec![
+                    Line::from(vec![
+                        "Configured memory: ".fg(INFO_COLOR),
+                        current_vm
+                            .mem
+                            .map(format_bytes)
+                            .unwrap_or("-".to_owned())
+                            .into(),
+                    ]),
+                    Line::from(vec![
+                        "Effective memory:  ".fg(INFO_COLOR),
+                        current_vm
+                            .balloon
+                            .map(format_bytes)
+                            .unwrap_or("-".to_owned())
+                            .into(),
+                    ]),
+                    Line::from(""),
+                    Line::from(vec![
+                        "New size (ie. 192M, 1G): ".fg(INFO_COLOR),
+                        format!("{target}_").into(),
+                    ]),
+                ];
+
+                render_popup(
+                    frame,
+                    &format!(" Memory balloon of VM '{}' ", current_vm.name),
+                    Paragraph::new(lines),
+                    None,
+                );
+            }
+        }
+
+        Screen::QmpUnavailable { vm_name, reason } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            render_popup(
+                frame,
+                &format!(" ⚠️ QMP unavailable for VM '{}' ⚠️ ", vm_name),
+                Paragraph::new(vec![Line::from(reason).centered()]),
+                None,
+            );
+        }
+
+        Screen::AgentUnavailable { vm_name, reason } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            render_popup(
+                frame,
+                &format!(" ⚠️ Guest agent unavailable for VM '{}' ⚠️ ", vm_name),
+                Paragraph::new(vec![Line::from(reason).centered()]),
+                None,
+            );
+        }
+
+        Screen::QmpFailed { vm_name, error } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            render_popup(
+                frame,
+                &format!(" ❌ QMP command failed on VM '{}' ❌ ", vm_name),
+                Paragraph::new(vec![Line::from(error).centered()]),
+                None,
+            );
+        }
+
+        Screen::Console => {
+            render_header(frame, app, header_chunk);
+            render_console(frame, app, main_chunk);
+        }
+
+        Screen::Log => {
+            render_header(frame, app, header_chunk);
+            render_log(frame, app, main_chunk);
+        }
+
+        Screen::StartLog { vm_name, offset } => {
+            render_header(frame, app, header_chunk);
+            render_start_log(frame, app, &vm_name, offset, main_chunk);
+        }
+
+        Screen::Activity {
+            vm_filter,
+            min_severity,
+            offset,
+        } => {
+            render_header(frame, app, header_chunk);
+            render_activity(
+                frame,
+                app,
+                vm_filter.as_deref(),
+                min_severity,
+                offset,
+                main_chunk,
+            );
+        }
+
+        Screen::ConsoleUnavailable { vm_name, reason } => {
+            render_main(frame, app, header_chunk, main_chunk);
+
+            render_popup(
+                frame,
+                &format!(" ⚠️ Console unavailable for VM '{}' ⚠️ ", vm_name),
+                Paragraph::new(vec![Line::from(reason).centered()]),
+                None,
+            );
+        }
     }
 }
 
-fn render_header(frame: &mut Frame, _app: &mut State, area: Rect) {
+/// Renders the header, the VMs list and the details of the selected VM
+fn render_main(frame: &mut Frame, app: &mut State, header_area: Rect, area: Rect) {
+    render_header(frame, app, header_area);
+
+    let details = app
+        .selected_vm()
+        .map(|vm| vm_details(app, vm))
+        .unwrap_or_default();
+    // The output of startnb.sh is shown live while the selected VM is starting
+    let starting_vm = app
+        .selected_vm()
+        .filter(|vm| matches!(vm.state, VmState::Starting))
+        .map(|vm| vm.name.clone());
+    let show_history = app.selected_vm().is_some_and(|vm| !vm.history.is_empty());
+    let details_height = if show_history {
+        details.len().max(HISTORY_PANE_HEIGHT)
+    } else {
+        details.len()
+    };
+    // Adding 2 to account for the top and bottom borders
+    let [vms_list_chunk, start_log_chunk, details_chunk] = Layout::vertical([
+        Constraint::Fill(1),
+        Constraint::Length(if starting_vm.is_some() {
+            START_LOG_PANE_HEIGHT + 2
+        } else {
+            0
+        }),
+        Constraint::Length(details_height as u16 + 2),
+    ])
+    .areas(area);
+
+    render_vms_list(frame, app, vms_list_chunk);
+    if let Some(vm_name) = starting_vm {
+        render_start_log(frame, app, &vm_name, 0, start_log_chunk);
+    }
+    if show_history {
+        let [details_chunk, history_chunk] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Percentage(40)])
+                .areas(details_chunk);
+        render_details(frame, app, details, details_chunk);
+        render_history(frame, app, history_chunk);
+    } else {
+        render_details(frame, app, details, details_chunk);
+    }
+}
+
+/// Renders the resource usage history of the selected VM as sparklines
+fn render_history(frame: &mut Frame, app: &State, area: Rect) {
+    let Some(vm) = app.selected_vm() else {
+        return;
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " History (last {}) ",
+            format_duration(app.config.history_window)
+        ))
+        .border_type(Rounded)
+        .title_alignment(Alignment::Center)
+        .fg(Color::Indexed(74));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let samples = vm.history.downsample(inner.width as usize);
+    let latest = vm.history.latest().copied().unwrap_or_default();
+    let max = |value: fn(&Sample) -> u64| samples.iter().map(value).max().unwrap_or(0);
+    let max_rss = max(|sample| sample.rss);
+    let max_disks_io = max(|sample| sample.disks_io);
+    let graphs = [
+        (
+            format!(
+                "CPU {}% (max {}%)",
+                latest.cpu,
+                max(|sample| sample.cpu as u64)
+            ),
+            samples
+                .iter()
+                .map(|sample| sample.cpu as u64)
+                .collect::<Vec<_>>(),
+            100,
+            RUNNING_VM_FG,
+        ),
+        (
+            format!(
+                "RSS {} (max {})",
+                format_bytes(latest.rss),
+                format_bytes(max_rss)
+            ),
+            samples.iter().map(|sample| sample.rss).collect(),
+            vm.mem.unwrap_or(0).max(max_rss),
+            STARTING_VM_FG,
+        ),
+        (
+            format!(
+                "Disk I/O {}/s (max {}/s)",
+                format_bytes(latest.disks_io),
+                format_bytes(max_disks_io)
+            ),
+            samples.iter().map(|sample| sample.disks_io).collect(),
+            max_disks_io,
+            STOPPED_VM_FG,
+        ),
+    ];
+
+    let areas = Layout::vertical([Constraint::Fill(1); 3]).split(inner);
+    for ((title, data, max, color), area) in graphs.into_iter().zip(areas.iter()) {
+        let [title_area, sparkline_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(*area);
+        frame.render_widget(Line::from(title).fg(INFO_COLOR), title_area);
+        frame.render_widget(
+            Sparkline::default().data(&data).max(max.max(1)).fg(color),
+            sparkline_area,
+        );
+    }
+}
+
+/// Renders the output of startnb.sh for `vm_name`, `offset` lines being hidden at the bottom
+fn render_start_log(frame: &mut Frame, app: &State, vm_name: &str, offset: usize, area: Rect) {
+    let start_log = app
+        .vms
+        .iter()
+        .find(|vm| vm.name == vm_name)
+        .map(|vm| vm.start_log.as_slice())
+        .unwrap_or_default();
+
+    let hints = match app.current_screen {
+        Screen::StartLog { .. } => Line::from(vec![
+            " ".into(),
+            "<↑|↓|PgUp|PgDn|Home|End>".fg(ACTION_COLOR),
+            " Scroll ".into(),
+            "<Esc>".fg(ACTION_COLOR),
+            " Back ".into(),
+        ]),
+        _ => Line::from(vec![
+            " ".into(),
+            "<o>".fg(ACTION_COLOR),
+            " Full output ".into(),
+        ]),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" startnb.sh output of '{vm_name}' "))
+        .title_bottom(hints)
+        .border_type(Rounded)
+        .title_alignment(Alignment::Center)
+        .fg(Color::Indexed(74));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let last = start_log.len().saturating_sub(offset);
+    let first = last.saturating_sub(inner.height as usize);
+    let lines: Vec<Line> = if start_log.is_empty() {
+        vec![Line::from("No output").fg(INFO_COLOR)]
+    } else {
+        start_log[first..last]
+            .iter()
+            .map(|line| {
+                let text = Line::from(line.text.clone());
+                if line.stderr {
+                    text.fg(INVALID_CONF_VM_FG)
+                } else {
+                    text.reset()
+                }
+            })
+            .collect()
+    };
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_header(frame: &mut Frame, app: &mut State, area: Rect) {
+    let (apparent, allocated) = app.images_total();
     let [tier1, tier2, tier3] = Layout::horizontal([
         Constraint::Percentage(33),
         Constraint::Percentage(33),
@@ -97,22 +803,129 @@ fn render_header(frame: &mut Frame, _app: &mut State, area: Rect) {
     ])
     .areas(area);
     frame.render_widget(
-        Paragraph::new(Line::from(vec!["Version: ".fg(INFO_COLOR), VERSION.into()]))
-            .block(Block::new().padding(Padding::left(1))),
+        Paragraph::new(vec![
+            Line::from(vec!["Version: ".fg(INFO_COLOR), VERSION.into()]),
+            Line::from(vec![
+                "Images: ".fg(INFO_COLOR),
+                format!(
+                    "{} allocated ({} apparent)",
+                    format_bytes(allocated),
+                    format_bytes(apparent)
+                )
+                .into(),
+            ]),
+        ])
+        .block(Block::new().padding(Padding::left(1))),
         tier1,
     );
+    render_key_hints(frame, tier2);
+
+    render_host_summary(frame, app, tier3);
+
+    // TODO: load logo in tier3 (9x13 ?), next to the host summary
+    // Only works with the "official" ratatui crate, not my github fork :(
+    //frame.render_stateful_widget(StatefulImage::default(), tier3, &mut app.logo);
+}
+
+/// Renders the cores and memory of the host, and how much of them is committed to the running VMs
+fn render_host_summary(frame: &mut Frame, app: &State, area: Rect) {
+    let (committed_cores, committed_mem) = app.committed_resources();
+    let host_cores = vm::proc::host_cores();
+    let running = app
+        .vms
+        .iter()
+        .filter(|vm| vm.is_running() || matches!(vm.state, VmState::Starting))
+        .count();
+
+    let mut lines = vec![Line::from(vec![
+        "Host: ".fg(INFO_COLOR),
+        format!("{host_cores} cores").into(),
+        match app.host_mem {
+            Some(host_mem) => format!(
+                ", {} available / {}",
+                format_bytes(host_mem.available),
+                format_bytes(host_mem.total)
+            ),
+            None => String::new(),
+        }
+        .into(),
+    ])];
+    let overcommitted = app.host_mem.is_some_and(|host_mem| {
+        committed_mem as f64 > host_mem.total as f64 * app.config.mem_overcommit_ratio
+    });
+    lines.push(Line::from(vec![
+        "Committed: ".fg(INFO_COLOR),
+        format!(
+            "{committed_cores} cores, {} ({running} VMs)",
+            format_bytes(committed_mem)
+        )
+        .fg(if overcommitted || committed_cores > host_cores {
+            INVALID_CONF_VM_FG
+        } else {
+            Color::Reset
+        }),
+    ]));
     frame.render_widget(
-        Paragraph::new(Text::from(vec![
-            Line::from(vec!["<Esc|q>".fg(ACTION_COLOR), " Quit".into()]),
-            Line::from(vec!["    <s>".fg(ACTION_COLOR), " Start/Stop".into()]),
-            Line::from(vec!["    <d>".fg(ACTION_COLOR), " Delete".into()]),
-        ])),
-        tier2,
+        Paragraph::new(lines).block(Block::new().padding(Padding::left(1))),
+        area,
     );
+}
 
-    // TODO: load logo in tier3 (9x13 ?)
-    // Only works with the "official" ratatui crate, not my github fork :(
-    //frame.render_stateful_widget(StatefulImage::default(), tier3, &mut _app.logo);
+/// Keys of the VMs list, shown in the help popup
+const KEY_HINTS: [(&str, &str); 22] = [
+    ("<Esc|q>", "Quit"),
+    ("<s>", "Start/Stop"),
+    ("<d>", "Delete"),
+    ("<f>", "Port forwards"),
+    ("<g>", "Guest shutdown"),
+    ("<h>", "Hibernate"),
+    ("<H>", "Saved states"),
+    ("<i>", "Images"),
+    ("<p>", "Check QMP port"),
+    ("<k>", "Send keys"),
+    ("<b>", "Balloon"),
+    ("<a>", "Attach disk"),
+    ("<x>", "Detach disk"),
+    ("<c>", "Console"),
+    ("<C>", "Raw console"),
+    ("<l>", "Console log"),
+    ("<o>", "startnb.sh output"),
+    ("<A>", "Activity log"),
+    ("<R>", "Console recordings"),
+    ("<e>", "Run console script"),
+    ("<E>", "Script progress"),
+    ("<?>", "Help"),
+];
+
+/// Most used keys of the VMs list, shown in the header
+const HEADER_KEY_HINTS: [(&str, &str); 6] = [
+    ("<Esc|q>", "Quit"),
+    ("<s>", "Start/Stop"),
+    ("<c>", "Console"),
+    ("<l>", "Console log"),
+    ("<A>", "Activity log"),
+    ("<?>", "All keys"),
+];
+
+/// Renders the most used key hints in columns of `area.height` lines
+fn render_key_hints(frame: &mut Frame, area: Rect) {
+    let lines_per_column = (area.height as usize).max(1);
+    let columns: Vec<_> = HEADER_KEY_HINTS.chunks(lines_per_column).collect();
+    let columns_areas = Layout::horizontal(vec![Constraint::Fill(1); columns.len()]).split(area);
+
+    for (column, column_area) in columns.iter().zip(columns_areas.iter()) {
+        let key_width = column.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        let lines: Vec<_> = column
+            .iter()
+            .map(|(key, action)| {
+                Line::from(vec![
+                    format!("{key:>key_width$}").fg(ACTION_COLOR),
+                    format!(" {action}").into(),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(Text::from(lines)), *column_area);
+    }
 }
 
 fn render_vms_list(frame: &mut Frame, app: &mut State, area: Rect) {
@@ -121,14 +934,42 @@ fn render_vms_list(frame: &mut Frame, app: &mut State, area: Rect) {
         .iter()
         .map(|vm| {
             let (state_str, state_color) = vm.state();
-            Row::new(vec![vm.name.clone(), state_str]).style(Style::new().fg(state_color))
+            Row::new(vec![
+                Cell::from(vm.name.clone()),
+                Cell::from(state_str),
+                vm_cpu(vm),
+                Cell::from(vm_mem(vm)),
+                Cell::from(vm.rss.map(format_bytes).unwrap_or("-".to_owned())),
+                Cell::from(vm_disks_io(vm)),
+                Cell::from(vm_net_io(vm)),
+                Cell::from(match vm.agent_available {
+                    Some(true) => "✓",
+                    Some(false) => "✗",
+                    None => "-",
+                }),
+            ])
+            .style(Style::new().fg(state_color))
         })
         .collect();
-    let widths = [Constraint::Min(5), Constraint::Max(24)];
+    let widths = [
+        Constraint::Min(5),
+        Constraint::Max(24),
+        Constraint::Length(4),
+        Constraint::Max(20),
+        Constraint::Max(8),
+        Constraint::Max(24),
+        Constraint::Max(24),
+        Constraint::Length(5),
+    ];
     let table = Table::new(rows, widths)
         .column_spacing(DEFAULT_SPACING_PADDING)
         .fg(Color::Indexed(74))
-        .header(Row::new(vec!["NAME", "STATE"]).style(Style::new().white()))
+        .header(
+            Row::new(vec![
+                "NAME", "STATE", "CPU", "MEM", "RSS", "DISK I/O", "NET I/O", "AGENT",
+            ])
+            .style(Style::new().white()),
+        )
         .block(
             Block::default()
                 .borders(Borders::ALL)
@@ -151,6 +992,661 @@ fn render_vms_list(frame: &mut Frame, app: &mut State, area: Rect) {
     frame.render_stateful_widget(table, area, &mut app.table_state);
 }
 
+/// CPU usage of a running VM, coloured according to the load
+fn vm_cpu(vm: &Vm) -> Cell<'static> {
+    if !vm.is_running() {
+        return Cell::from("-");
+    }
+    let color = match vm.cpu_usage {
+        0..50 => RUNNING_VM_FG,
+        50..80 => Color::Yellow,
+        _ => INVALID_CONF_VM_FG,
+    };
+    Cell::from(format!("{:>3}%", vm.cpu_usage)).fg(color)
+}
+
+/// Configured memory, followed by the effective memory when the balloon has been queried
+fn vm_mem(vm: &Vm) -> String {
+    let configured = vm.mem.map(format_bytes).unwrap_or("-".to_owned());
+    match vm.balloon {
+        Some(actual) => format!("{configured} ({})", format_bytes(actual)),
+        None => configured,
+    }
+}
+
+/// Total read and write throughput of the VM disks
+fn vm_disks_io(vm: &Vm) -> String {
+    match vm.total_disks_io() {
+        Some((rd, wr)) => format!(
+            "R {}/s W {}/s",
+            format_bytes(rd as u64),
+            format_bytes(wr as u64)
+        ),
+        None => "-".to_owned(),
+    }
+}
+
+/// Received and transmitted throughput of the tap interface of a bridged VM
+fn vm_net_io(vm: &Vm) -> String {
+    match vm.net_io {
+        Some(net_io) => format!(
+            "RX {}/s TX {}/s",
+            format_bytes(net_io.rx_bps as u64),
+            format_bytes(net_io.tx_bps as u64)
+        ),
+        None => "-".to_owned(),
+    }
+}
+
+/// A text bar representing a percentage, like "[|||||     ]  50%"
+fn usage_bar(percent: f64) -> String {
+    const BAR_WIDTH: usize = 20;
+    let filled = ((percent / 100.0 * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    format!(
+        "[{}{}] {percent:>3.0}%",
+        "|".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled)
+    )
+}
+
+/// Lines describing `vm`, displayed in the details pane
+fn vm_details(app: &State, vm: &Vm) -> Vec<Line<'static>> {
+    let field = |name: &str, value: String| {
+        Line::from(vec![format!("{name:<15}").fg(INFO_COLOR), value.into()])
+    };
+    let mut lines = vec![
+        field("Kernel", vm.kernel.clone().unwrap_or("-".to_owned())),
+        field(
+            "Image",
+            match (&vm.img, &vm.img_usage) {
+                (Some(img), Some(usage)) => format!("{img} {}", usage.summary()),
+                (Some(img), None) => img.clone(),
+                (None, _) => "-".to_owned(),
+            },
+        ),
+        field("Memory", vm_mem(vm)),
+        field(
+            "Cores",
+            vm.cores
+                .map(|cores| cores.to_string())
+                .unwrap_or("-".to_owned()),
+        ),
+        field(
+            "QMP port",
+            vm.qmp_port
+                .map(|port| port.to_string())
+                .unwrap_or("-".to_owned()),
+        ),
+        field(
+            "QMP socket",
+            match &vm.qmp_socket {
+                Some(qmp_socket) if vm.qmp_socket_present => format!("{qmp_socket} (present)"),
+                Some(qmp_socket) => format!("{qmp_socket} (absent)"),
+                None => "-".to_owned(),
+            },
+        ),
+        field(
+            "Port forwards",
+            vm.hostfwd.clone().unwrap_or("-".to_owned()),
+        ),
+    ];
+    for issue in app.qmp_port_issues.get(&vm.name).into_iter().flatten() {
+        lines.push(field("QMP issue", issue.clone()).fg(INVALID_CONF_VM_FG));
+    }
+    if let VmState::InvalidConfiguration { cause } = &vm.state {
+        lines.push(field("Error", cause.clone()).fg(INVALID_CONF_VM_FG));
+    }
+    if let Some(run) = app.scripts.get(&vm.name) {
+        let line = field("Script", run.progress());
+        lines.push(match run.result {
+            Some(Err(_)) => line.fg(INVALID_CONF_VM_FG),
+            _ => line,
+        });
+    }
+    if let Some(boot_duration) = vm.boot_duration {
+        lines.push(field(
+            "Boot time",
+            format!("{:.1}s", boot_duration.as_secs_f64()),
+        ));
+    }
+    if !vm.attached_disks.is_empty() {
+        lines.push(field(
+            "Attached disks",
+            vm.attached_disks
+                .iter()
+                .map(|disk| format!("{} ({})", disk.image, disk.id))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ));
+    }
+    for (idx, usage) in vm.vcpus_usage.iter().enumerate() {
+        lines.push(field(&format!("vCPU {idx}"), usage_bar(*usage)));
+    }
+    if let Some(info) = &vm.guest_info {
+        if let Some(os) = &info.os {
+            lines.push(field("Guest OS", os.clone()));
+        }
+        if let Some(hostname) = &info.hostname {
+            lines.push(field("Guest hostname", hostname.clone()));
+        }
+        if !info.ip_addresses.is_empty() {
+            lines.push(field("Guest IPs", info.ip_addresses.join(", ")));
+        }
+        for filesystem in &info.filesystems {
+            let usage = match (filesystem.used_bytes, filesystem.total_bytes) {
+                (Some(used), Some(total)) if total > 0 => format!(
+                    "{} / {} ({}%)",
+                    format_bytes(used),
+                    format_bytes(total),
+                    used * 100 / total
+                ),
+                _ => "?".to_owned(),
+            };
+            lines.push(field(&format!("FS {}", filesystem.mountpoint), usage));
+        }
+    }
+    if vm.bridgenet.is_some() && vm.is_running() {
+        lines.push(field(
+            "Tap",
+            match (&vm.tap, vm.net_stats) {
+                (Some(tap), Some((_, stats))) => format!(
+                    "{tap} (RX {} / {} packets, TX {} / {} packets)",
+                    format_bytes(stats.rx_bytes),
+                    stats.rx_packets,
+                    format_bytes(stats.tx_bytes),
+                    stats.tx_packets
+                ),
+                (Some(tap), None) => tap.clone(),
+                // The file descriptors of QEMU can't be read when it runs as another user
+                (None, _) => "not found".to_owned(),
+            },
+        ));
+    }
+    if let Some(net_io) = vm.net_io {
+        lines.push(field(
+            "Network",
+            format!(
+                "RX {}/s ({:.0} pkt/s)  TX {}/s ({:.0} pkt/s)",
+                format_bytes(net_io.rx_bps as u64),
+                net_io.rx_pps,
+                format_bytes(net_io.tx_bps as u64),
+                net_io.tx_pps
+            ),
+        ));
+    }
+    for disk in &vm.disks_io {
+        lines.push(field(
+            &format!("Disk {}", disk.device),
+            format!(
+                "R {}/s ({:.0} IOPS)  W {}/s ({:.0} IOPS)",
+                format_bytes(disk.rd_bps as u64),
+                disk.rd_iops,
+                format_bytes(disk.wr_bps as u64),
+                disk.wr_iops
+            ),
+        ));
+    }
+    lines
+}
+
+fn render_details(frame: &mut Frame, app: &State, lines: Vec<Line>, area: Rect) {
+    let title = match app.selected_vm() {
+        Some(vm) => format!(" {} ", vm.name),
+        None => " No VM selected ".to_owned(),
+    };
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .padding(Padding {
+                    left: DEFAULT_SPACING_PADDING,
+                    right: DEFAULT_SPACING_PADDING,
+                    top: 0,
+                    bottom: 0,
+                })
+                .border_type(Rounded)
+                .title_alignment(Alignment::Center)
+                .fg(Color::Indexed(74)),
+        ),
+        area,
+    );
+}
+
+/// Renders the entries of the activity log matching the filters, `offset` entries being hidden at the bottom
+fn render_activity(
+    frame: &mut Frame,
+    app: &State,
+    vm_filter: Option<&str>,
+    min_severity: Severity,
+    offset: usize,
+    area: Rect,
+) {
+    let entries: Vec<_> = app.activity.filtered(vm_filter, min_severity).collect();
+
+    let filters = format!(
+        " [{}] [{}+] ",
+        vm_filter.unwrap_or("all VMs"),
+        min_severity.as_str()
+    );
+    let status = match &app.activity.write_error {
+        Some(error) => Line::from(format!(" {error} ")).fg(INVALID_CONF_VM_FG),
+        None => Line::from(vec![
+            " ".into(),
+            "<↑|↓|PgUp|PgDn|Home|End>".fg(ACTION_COLOR),
+            " Scroll ".into(),
+            "<v>".fg(ACTION_COLOR),
+            " VM ".into(),
+            "<s>".fg(ACTION_COLOR),
+            " Severity ".into(),
+            "<Esc>".fg(ACTION_COLOR),
+            " Back ".into(),
+        ]),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Activity log ({}) ", app.activity.path.display()))
+        .title_bottom(status)
+        .title_bottom(Line::from(filters).right_aligned())
+        .border_type(Rounded)
+        .title_alignment(Alignment::Center)
+        .fg(Color::Indexed(74));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let last = entries.len().saturating_sub(offset);
+    let first = last.saturating_sub(inner.height as usize);
+    let lines: Vec<Line> = entries[first..last]
+        .iter()
+        .map(|entry| {
+            let severity_color = match entry.severity {
+                Severity::Info => RUNNING_VM_FG,
+                Severity::Warning => STOPPED_VM_FG,
+                Severity::Error => INVALID_CONF_VM_FG,
+            };
+            Line::from(vec![
+                entry.time.clone().fg(INFO_COLOR),
+                format!(" {:<7} ", entry.severity.as_str()).fg(severity_color),
+                format!("{} ", entry.vm_name.as_deref().unwrap_or("-")).bold(),
+                entry.message.clone().reset(),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Renders the serial console of `app.console`, the terminal emulator being resized to fit `area`
+fn render_console(frame: &mut Frame, app: &mut State, area: Rect) {
+    let escape = app.config.console_escape.clone();
+    let search_key = app.config.console_search.clone();
+    let record_key = app.config.console_record.clone();
+    let Some(console) = app.console.as_mut() else {
+        return;
+    };
+
+    let status = match (&console.closed, &console.search) {
+        (Some(reason), _) => {
+            Line::from(format!(" Console closed: {reason} ")).fg(INVALID_CONF_VM_FG)
+        }
+        _ if console.record_error.is_some() => Line::from(format!(
+            " {} ",
+            console.record_error.as_deref().unwrap_or_default()
+        ))
+        .fg(INVALID_CONF_VM_FG),
+        (None, Some(search)) => Line::from(vec![
+            format!(" {} ", search.status().unwrap_or_default()).into(),
+            "</>".fg(ACTION_COLOR),
+            " Search ".into(),
+            "<n|N>".fg(ACTION_COLOR),
+            " Next/Previous ".into(),
+            "<↑|↓|PgUp|PgDn>".fg(ACTION_COLOR),
+            " Scroll ".into(),
+            "<Esc>".fg(ACTION_COLOR),
+            " Resume ".into(),
+        ]),
+        (None, None) => Line::from(vec![
+            " Press ".into(),
+            escape.fg(ACTION_COLOR),
+            " to go back to the VMs list, ".into(),
+            search_key.fg(ACTION_COLOR),
+            " to search, ".into(),
+            record_key.fg(ACTION_COLOR),
+            " to start/stop recording ".into(),
+        ]),
+    };
+    let recording = console.recorder.as_ref().map(|recorder| {
+        Line::from(format!(" ● REC {} ", recorder.path.display()))
+            .fg(INVALID_CONF_VM_FG)
+            .right_aligned()
+    });
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Console of '{}' ", console.vm_name))
+        .title_bottom(status)
+        .title_bottom(recording.unwrap_or_default())
+        .border_type(Rounded)
+        .title_alignment(Alignment::Center)
+        .fg(Color::Indexed(74));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    console.resize(inner.height, inner.width);
+    let screen = console.screen();
+    let lines: Vec<Line> = (0..inner.height)
+        .map(|row| console_line(screen, row, inner.width, &console.row_highlights(row)))
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+
+    if console.closed.is_none() && console.search.is_none() && !screen.hide_cursor() {
+        let (row, col) = screen.cursor_position();
+        frame.set_cursor_position((inner.x + col, inner.y + row));
+    }
+}
+
+/// Renders the progress of the last console script run on `vm_name`
+fn render_script(frame: &mut Frame, app: &State, vm_name: &str, area: Rect) {
+    let Some(run) = app.scripts.get(vm_name) else {
+        return;
+    };
+
+    let (status, status_color) = match &run.result {
+        None => (
+            format!("Running for {}s", run.started.elapsed().as_secs()),
+            STARTING_VM_FG,
+        ),
+        Some(Ok(())) => ("Passed".to_owned(), RUNNING_VM_FG),
+        Some(Err(err)) => (format!("Failed: {err}"), INVALID_CONF_VM_FG),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Script '{}' on '{vm_name}' ", run.name))
+        .title_bottom(Line::from(format!(" {status} ")).fg(status_color))
+        .title_bottom(
+            Line::from(vec![
+                " ".into(),
+                "<x>".fg(ACTION_COLOR),
+                " Abort ".into(),
+                "<r>".fg(ACTION_COLOR),
+                " Run again ".into(),
+                "<Esc>".fg(ACTION_COLOR),
+                " Back ".into(),
+            ])
+            .right_aligned(),
+        )
+        .border_type(Rounded)
+        .title_alignment(Alignment::Center)
+        .fg(Color::Indexed(74));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let lines: Vec<Line> = run
+        .steps
+        .iter()
+        .map(|(line, status)| {
+            let (icon, color) = match status {
+                StepStatus::Pending => ("·", Color::Gray),
+                StepStatus::Running => ("⏳", STARTING_VM_FG),
+                StepStatus::Passed => ("✔", RUNNING_VM_FG),
+                StepStatus::Failed(_) => ("✘", INVALID_CONF_VM_FG),
+            };
+            let mut spans = vec![format!("{icon} ").fg(color), line.clone().reset()];
+            if let StepStatus::Failed(err) = status {
+                spans.push(format!("  {err}").fg(INVALID_CONF_VM_FG));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    // The running step stays visible
+    let current = run
+        .steps
+        .iter()
+        .rposition(|(_, status)| *status != StepStatus::Pending)
+        .unwrap_or(0);
+    let scroll = (current + 1).saturating_sub(inner.height as usize);
+    frame.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), inner);
+}
+
+/// Renders the console recording played by `app.player`
+fn render_player(frame: &mut Frame, app: &mut State, area: Rect) {
+    let Some(player) = app.player.as_ref() else {
+        return;
+    };
+
+    let status = Line::from(vec![
+        format!(
+            " {} {:.1}s/{:.1}s x{} ",
+            if player.paused { "⏸" } else { "▶" },
+            player.position,
+            player.duration,
+            SPEEDS[player.speed]
+        )
+        .into(),
+        "<Space>".fg(ACTION_COLOR),
+        " Play/Pause ".into(),
+        "<+|->".fg(ACTION_COLOR),
+        " Speed ".into(),
+        "<←|→>".fg(ACTION_COLOR),
+        " Seek ".into(),
+        "<Home>".fg(ACTION_COLOR),
+        " Restart ".into(),
+        "<Esc>".fg(ACTION_COLOR),
+        " Back ".into(),
+    ]);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " {} ",
+            player.title.clone().unwrap_or_else(|| player
+                .path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned())
+        ))
+        .title_bottom(status)
+        .border_type(Rounded)
+        .title_alignment(Alignment::Center)
+        .fg(Color::Indexed(74));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    // The recording is displayed with its own size, clipped to the pane
+    let screen = player.screen();
+    let (rows, cols) = screen.size();
+    let lines: Vec<Line> = (0..rows.min(inner.height))
+        .map(|row| console_line(screen, row, cols.min(inner.width), &[]))
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Renders the console log of `app.log_view`
+fn render_log(frame: &mut Frame, app: &mut State, area: Rect) {
+    let Some(log_view) = app.log_view.as_mut() else {
+        return;
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Console log of '{}' ", log_view.vm_name))
+        .border_type(Rounded)
+        .title_alignment(Alignment::Center)
+        .fg(Color::Indexed(74));
+    let mut inner = block.inner(area);
+    let mut notice_area = None;
+    if log_view.notice.is_some() {
+        let [area, lines_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(inner);
+        notice_area = Some(area);
+        inner = lines_area;
+    }
+    log_view.height = inner.height as usize;
+
+    let first = log_view.first_visible_line();
+    let last = (first + log_view.height).min(log_view.lines.len());
+    let position = format!(
+        " {}{}-{}/{}{} ",
+        match &log_view.filter {
+            Some((name, _)) => format!("[{name}] "),
+            None => String::new(),
+        },
+        (first + 1).min(last),
+        last,
+        log_view.lines.len(),
+        if log_view.top.is_none() {
+            " (following)"
+        } else {
+            ""
+        }
+    );
+    let hints = match log_view.search.status() {
+        Some(status) if log_view.search.input.is_some() => Line::from(format!(" {status} ")),
+        status => Line::from(vec![
+            format!(" {} ", status.unwrap_or_default()).into(),
+            "<↑|↓|PgUp|PgDn>".fg(ACTION_COLOR),
+            " Scroll ".into(),
+            "<Home|End>".fg(ACTION_COLOR),
+            " Start/Follow ".into(),
+            "<b>".fg(ACTION_COLOR),
+            " Last boot ".into(),
+            "</|n|N>".fg(ACTION_COLOR),
+            " Search ".into(),
+            "<f>".fg(ACTION_COLOR),
+            " Filter ".into(),
+            "<Esc>".fg(ACTION_COLOR),
+            " Back ".into(),
+        ]),
+    };
+    frame.render_widget(
+        block
+            .title_bottom(hints)
+            .title_bottom(Line::from(position).right_aligned()),
+        area,
+    );
+    if let (Some(notice), Some(notice_area)) = (&log_view.notice, notice_area) {
+        frame.render_widget(Line::from(notice.clone()).fg(Color::Yellow), notice_area);
+    }
+
+    let lines: Vec<Line> = match &log_view.error {
+        Some(error) => vec![Line::from(error.clone()).fg(INVALID_CONF_VM_FG)],
+        None => log_view.lines[first.min(last)..last]
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                highlighted_line(line, log_view.search.line_matches(first + idx)).reset()
+            })
+            .collect(),
+    };
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Converts a row of the terminal emulator screen, consecutive cells with the same style being merged.
+/// `highlights` tells which columns are part of a search match (cf. `Console::row_highlights()`)
+fn console_line(
+    screen: &vt100::Screen,
+    row: u16,
+    width: u16,
+    highlights: &[Option<bool>],
+) -> Line<'static> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut text = String::new();
+    let mut style = Style::new();
+
+    for (col, cell) in (0..width).filter_map(|col| Some((col, screen.cell(row, col)?))) {
+        // The 2nd column of a wide character is already covered by the character
+        if cell.is_wide_continuation() {
+            continue;
+        }
+
+        let mut modifiers = Modifier::empty();
+        for (enabled, modifier) in [
+            (cell.bold(), Modifier::BOLD),
+            (cell.dim(), Modifier::DIM),
+            (cell.italic(), Modifier::ITALIC),
+            (cell.underline(), Modifier::UNDERLINED),
+            (cell.inverse(), Modifier::REVERSED),
+        ] {
+            if enabled {
+                modifiers |= modifier;
+            }
+        }
+        let cell_style = match highlights.get(col as usize) {
+            Some(Some(current)) => search_match_style(*current),
+            _ => Style::new()
+                .fg(console_color(cell.fgcolor()))
+                .bg(console_color(cell.bgcolor()))
+                .add_modifier(modifiers),
+        };
+
+        if cell_style != style && !text.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut text), style));
+        }
+        style = cell_style;
+        if cell.has_contents() {
+            text.push_str(cell.contents());
+        } else {
+            text.push(' ');
+        }
+    }
+    if !text.is_empty() {
+        spans.push(Span::styled(text, style));
+    }
+
+    Line::from(spans)
+}
+
+fn search_match_style(current: bool) -> Style {
+    Style::new().fg(Color::Black).bg(if current {
+        CURRENT_SEARCH_MATCH_BG
+    } else {
+        SEARCH_MATCH_BG
+    })
+}
+
+/// Splits `line` in spans, the search matches being highlighted
+fn highlighted_line(line: &str, matches: Vec<(std::ops::Range<usize>, bool)>) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut end = 0;
+    for (range, current) in matches {
+        if range.start > end {
+            spans.push(Span::raw(line[end..range.start].to_owned()));
+        }
+        spans.push(Span::styled(
+            line[range.clone()].to_owned(),
+            search_match_style(current),
+        ));
+        end = range.end;
+    }
+    spans.push(Span::raw(line[end..].to_owned()));
+    Line::from(spans)
+}
+
+fn console_color(color: vt100::Color) -> Color {
+    match color {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(idx) => Color::Indexed(idx),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+/// One line per item, the selected one being highlighted. `empty` is displayed when there is no item
+fn selectable_lines(items: Vec<String>, selected: usize, empty: &str) -> Vec<Line<'static>> {
+    if items.is_empty() {
+        return vec![Line::from(empty.to_owned()).centered()];
+    }
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let line = Line::from(format!("  {item}  "));
+            if idx == selected {
+                line.reversed()
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
 fn render_popup(frame: &mut Frame, title: &str, msg: Paragraph, confirmation: Option<bool>) {
     let area = get_centered_area_fit_to_content(frame, &msg);
 
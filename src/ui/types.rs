@@ -1,9 +1,24 @@
+use crate::app::activity::Severity;
+use crate::vm::qmp::PortForward;
+
 #[derive(Clone, PartialEq)]
 pub enum Screen {
     /// VMs List
     List,
+    /// Popup listing the keys of the VMs list
+    Help,
     /// Confirmation popup when deleting a VM. The boolean value indicates if "OK" has been selected
     DeleteConfirmation(bool),
+    /// Confirmation popup when shutting a VM down from the guest. The boolean value indicates if "OK" has been selected
+    ShutdownConfirmation(bool),
+    /// Confirmation popup when starting a VM would overcommit the memory of the host.
+    /// `committed` is the memory of the running VMs with this one, `total` the memory of the host
+    OvercommitConfirmation {
+        vm_name: String,
+        committed: u64,
+        total: u64,
+        ok: bool,
+    },
     /// Popup to show the error message when startnb.sh failed
     StartNbFailed {
         vm_name: String,
@@ -15,4 +30,106 @@ pub enum Screen {
         vm_name: String,
         error: String,
     },
+    /// Menu to send key combinations to a VM.
+    /// `selected` is the selected entry (the last one being the custom sequence),
+    /// `hold_time` is an index in `vm::qmp::HOLD_TIMES_MS`
+    SendKeys {
+        selected: usize,
+        hold_time: usize,
+        custom: String,
+    },
+    /// Popup to resize the memory balloon of a VM. `target` is the size typed by the user
+    Balloon {
+        target: String,
+    },
+    /// Popup to select an image (in `images/`) to attach to a VM
+    AttachDisk {
+        selected: usize,
+    },
+    /// Popup to select a disk to detach from a VM
+    DetachDisk {
+        selected: usize,
+    },
+    /// Popup listing the QMP port issues of a VM.
+    /// When `free_port` is set, the user can choose ("OK" selected if `ok` is true) to use it instead
+    QmpPortIssues {
+        issues: Vec<String>,
+        free_port: Option<u16>,
+        ok: bool,
+    },
+    /// Port forwards editor of a running VM. `input` is set while a new port forward is typed
+    PortForwards {
+        netdev: Option<String>,
+        forwards: Vec<PortForward>,
+        selected: usize,
+        input: Option<String>,
+    },
+    /// List of the images in `images/` with their disk usage
+    Images {
+        selected: usize,
+    },
+    /// List of the saved states of the hibernated VMs
+    SavedStates {
+        selected: usize,
+    },
+    /// Popup explaining why a QMP based action can't be used
+    QmpUnavailable {
+        vm_name: String,
+        reason: String,
+    },
+    /// Popup explaining why the guest agent can't be used
+    AgentUnavailable {
+        vm_name: String,
+        reason: String,
+    },
+    /// Popup to show the error message when a QMP command failed
+    QmpFailed {
+        vm_name: String,
+        error: String,
+    },
+    /// Serial console of a VM (cf. `State::console`)
+    Console,
+    /// Console log viewer of a VM (cf. `State::log_view`)
+    Log,
+    /// Output of startnb.sh the last time `vm_name` was started (cf. `Vm::start_log`).
+    /// `offset` is the number of lines hidden at the bottom, 0 meaning following the output
+    StartLog {
+        vm_name: String,
+        offset: usize,
+    },
+    /// Activity log (cf. `State::activity`), only showing the entries of `vm_filter` (if set)
+    /// with at least `min_severity`. `offset` is the number of entries hidden at the bottom
+    Activity {
+        vm_filter: Option<String>,
+        min_severity: Severity,
+        offset: usize,
+    },
+    /// Popup to select a console recording (in `recordings/`) to play
+    Recordings {
+        selected: usize,
+    },
+    /// Player of a console recording (cf. `State::player`)
+    Player,
+    /// Popup to show the error message when a recording can't be played or deleted
+    RecordingFailed {
+        error: String,
+    },
+    /// Popup to select a console script (in `scripts/<name>/`) to run on the selected VM
+    Scripts {
+        selected: usize,
+    },
+    /// Progress of the last console script run on `vm_name` (cf. `State::scripts`)
+    Script {
+        vm_name: String,
+    },
+    /// Popup to show the error message when a console script can't be run
+    ScriptFailed {
+        vm_name: String,
+        error: String,
+    },
+    /// Popup explaining why the serial console can't be used
+    ConsoleUnavailable {
+        vm_name: String,
+        reason: String,
+    },
 }
@@ -1,4 +1,8 @@
-use std::{fs::DirEntry, path::PathBuf};
+use std::{
+    fs::DirEntry,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use crate::vm::Vm;
 
@@ -44,6 +48,42 @@ pub fn vm_from_conf(
     Ok(vm)
 }
 
+/// Sets `key` to `value` in the VM configuration file `conf_file`.
+/// The existing `key=...` line is replaced, or a new line is added at the end of the file.
+pub fn set_conf_value(conf_file: &str, key: &str, value: &str) -> Result<(), String> {
+    let conf = std::fs::read_to_string(conf_file)
+        .map_err(|err| format!("Failed to read {conf_file}: {err}"))?;
+
+    let mut found = false;
+    let mut lines: Vec<String> = conf
+        .lines()
+        .map(|line| {
+            if !line.starts_with('#')
+                && line
+                    .split_once('=')
+                    .is_some_and(|(line_key, _)| line_key == key)
+                && !found
+            {
+                found = true;
+                format!("{key}={value}")
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(format!("{key}={value}"));
+    }
+
+    std::fs::write(conf_file, lines.join("\n") + "\n")
+        .map_err(|err| format!("Failed to write {conf_file}: {err}"))
+}
+
+/// Returns true if nothing is listening on `localhost:port`
+pub fn is_port_free(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
 pub fn files_in_directory(directory: &str) -> Result<Vec<DirEntry>, Box<dyn std::error::Error>> {
     let res: Vec<_> = std::fs::read_dir(directory)?
         .filter_map(|res_dir_entry| res_dir_entry.ok())
@@ -52,6 +92,120 @@ pub fn files_in_directory(directory: &str) -> Result<Vec<DirEntry>, Box<dyn std:
     Ok(res)
 }
 
+/// Parses a memory size like "256" (MB, like QEMU's -m option), "512M" or "1G" into bytes
+pub fn parse_mem(input: &str) -> Result<u64, String> {
+    parse_size_with_unit(input, 1 << 20, "memory size")
+}
+
+/// Parses a file size like "4194304" (bytes), "512K" or "4M" into bytes
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    parse_size_with_unit(input, 1, "size")
+}
+
+/// Parses a size with an optional K, M, G or T suffix, `unit` being the multiplier without suffix
+fn parse_size_with_unit(input: &str, unit: u64, kind: &str) -> Result<u64, String> {
+    let value = input.trim_matches('"').trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((idx, suffix)) if suffix.is_ascii_alphabetic() => (
+            &value[..idx],
+            match suffix.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => {
+                    return Err(format!("unknown suffix '{suffix}' in {kind} '{input}'"));
+                }
+            },
+        ),
+        _ => (value, unit),
+    };
+    number
+        .parse::<u64>()
+        .map_err(|err| format!("cannot convert '{input}' into a {kind}: {err}"))?
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("{kind} '{input}' is too big"))
+}
+
+/// Parses a duration like "30" (seconds), "30s", "15m" or "1h"
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let value = input.trim_matches('"').trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((idx, suffix)) if suffix.is_ascii_alphabetic() => (
+            &value[..idx],
+            match suffix.to_ascii_lowercase() {
+                's' => 1,
+                'm' => 60,
+                'h' => 3600,
+                _ => return Err(format!("unknown suffix '{suffix}' in duration '{input}'")),
+            },
+        ),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .map_err(|err| format!("cannot convert '{input}' into a duration: {err}"))
+        .map(|number| Duration::from_secs(number.saturating_mul(multiplier)))
+}
+
+/// Formats a duration in the largest unit which keeps it exact (ie. "90s", "15m", "1h")
+pub fn format_duration(duration: Duration) -> String {
+    match duration.as_secs() {
+        secs if secs >= 3600 && secs % 3600 == 0 => format!("{}h", secs / 3600),
+        secs if secs >= 60 && secs % 60 == 0 => format!("{}m", secs / 60),
+        secs => format!("{secs}s"),
+    }
+}
+
+/// Formats a number of bytes in a human readable way (ie. "512M", "1.5G")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["", "K", "M", "G", "T"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value.fract() == 0.0 || value >= 100.0 {
+        format!("{value:.0}{}", UNITS[unit])
+    } else {
+        format!("{value:.1}{}", UNITS[unit])
+    }
+}
+
+/// Formats a time as "YYYY-MM-DD HH:MM:SS" (UTC)
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+
+    // Conversion of a number of days since 1970-01-01 to a date,
+    // cf. http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
 pub fn parse_bool(input: &str) -> Result<bool, String> {
     match input.trim_matches('"') {
         "true" | "True" | "y" | "Y" | "yes" | "Yes" => Ok(true),
@@ -59,3 +213,100 @@ pub fn parse_bool(input: &str) -> Result<bool, String> {
         _ => Err(format!("cannot convert '{input}' into a boolean")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mem_defaults_to_megabytes() {
+        assert_eq!(parse_mem("256"), Ok(256 << 20));
+        assert_eq!(parse_mem("\"512\""), Ok(512 << 20));
+        assert_eq!(parse_mem("512M"), Ok(512 << 20));
+        assert_eq!(parse_mem("1G"), Ok(1 << 30));
+    }
+
+    #[test]
+    fn parse_mem_errors() {
+        assert!(
+            parse_mem("512X")
+                .unwrap_err()
+                .contains("unknown suffix 'X' in memory size")
+        );
+        assert!(
+            parse_mem("twoG")
+                .unwrap_err()
+                .contains("into a memory size")
+        );
+        assert!(parse_mem("99999999999T").unwrap_err().contains("too big"));
+    }
+
+    #[test]
+    fn parse_size_defaults_to_bytes() {
+        assert_eq!(parse_size("1000"), Ok(1000));
+        assert_eq!(parse_size("\"4096\""), Ok(4096));
+    }
+
+    #[test]
+    fn parse_size_suffixes() {
+        assert_eq!(parse_size("64k"), Ok(64 << 10));
+        assert_eq!(parse_size("4M"), Ok(4 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("1t"), Ok(1 << 40));
+    }
+
+    #[test]
+    fn parse_size_errors() {
+        assert!(
+            parse_size("4MB")
+                .unwrap_err()
+                .contains("unknown suffix 'B'")
+        );
+        assert!(parse_size("").is_err());
+        assert!(parse_size("-1M").is_err());
+        assert!(parse_size("fourM").is_err());
+        assert!(parse_size("20000000T").unwrap_err().contains("too big"));
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("\"30s\""), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_duration("1H"), Ok(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn parse_duration_errors() {
+        assert!(
+            parse_duration("2d")
+                .unwrap_err()
+                .contains("unknown suffix 'd'")
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("1.5m").is_err());
+        assert!(parse_duration("-1s").is_err());
+        // Too big durations are saturated rather than rejected
+        assert_eq!(
+            parse_duration(&format!("{}h", u64::MAX)),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn format_duration_uses_the_largest_exact_unit() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_secs(900)), "15m");
+        assert_eq!(format_duration(Duration::from_secs(5400)), "90m");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+    }
+
+    #[test]
+    fn duration_round_trip() {
+        for secs in [1, 59, 60, 61, 3599, 3600, 86400] {
+            let duration = Duration::from_secs(secs);
+            assert_eq!(parse_duration(&format_duration(duration)), Ok(duration));
+        }
+    }
+}
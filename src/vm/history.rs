@@ -0,0 +1,146 @@
+//! Rolling history of the resource usage of a VM, sampled at each tick
+
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sample {
+    /// CPU usage (in %), cf. `Vm::cpu_usage`
+    pub cpu: u8,
+    /// Resident memory of the QEMU process (in bytes)
+    pub rss: u64,
+    /// Read + write throughput of the disks (in bytes/s)
+    pub disks_io: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    /// Samples sorted by time, the most recent being the last one
+    samples: VecDeque<Sample>,
+}
+
+impl History {
+    /// Adds a sample, only keeping the last `capacity` ones
+    pub fn push(&mut self, sample: Sample, capacity: usize) {
+        self.samples.push_back(sample);
+        while self.samples.len() > capacity.max(1) {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// All the samples reduced to `count` (at most), each one keeping the maximum values of
+    /// consecutive samples so that the spikes stay visible. The most recent is the last one
+    #[must_use]
+    pub fn downsample(&self, count: usize) -> Vec<Sample> {
+        let len = self.samples.len();
+        if len <= count {
+            return self.samples.iter().copied().collect();
+        }
+        (0..count)
+            .map(|bucket| {
+                self.samples
+                    .range(bucket * len / count..(bucket + 1) * len / count)
+                    .fold(Sample::default(), |max, sample| Sample {
+                        cpu: max.cpu.max(sample.cpu),
+                        rss: max.rss.max(sample.rss),
+                        disks_io: max.disks_io.max(sample.disks_io),
+                    })
+            })
+            .collect()
+    }
+
+    /// Sets the disks throughput of the latest sample
+    pub fn set_latest_disks_io(&mut self, disks_io: u64) {
+        if let Some(sample) = self.samples.back_mut() {
+            sample.disks_io = disks_io;
+        }
+    }
+
+    #[must_use]
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history_of(cpus: &[u8], capacity: usize) -> History {
+        let mut history = History::default();
+        for &cpu in cpus {
+            history.push(
+                Sample {
+                    cpu,
+                    ..Sample::default()
+                },
+                capacity,
+            );
+        }
+        history
+    }
+
+    fn cpus(samples: &[Sample]) -> Vec<u8> {
+        samples.iter().map(|sample| sample.cpu).collect()
+    }
+
+    #[test]
+    fn push_keeps_the_last_samples() {
+        let history = history_of(&[1, 2, 3, 4, 5], 3);
+        assert_eq!(cpus(&history.downsample(usize::MAX)), [3, 4, 5]);
+        assert_eq!(history.latest().map(|sample| sample.cpu), Some(5));
+
+        // At least one sample is kept
+        let history = history_of(&[1, 2], 0);
+        assert_eq!(cpus(&history.downsample(usize::MAX)), [2]);
+    }
+
+    #[test]
+    fn downsample_fewer_samples_than_count() {
+        let history = history_of(&[1, 2, 3], 10);
+        assert_eq!(cpus(&history.downsample(3)), [1, 2, 3]);
+        assert_eq!(cpus(&history.downsample(80)), [1, 2, 3]);
+    }
+
+    #[test]
+    fn downsample_keeps_the_maximum_of_each_bucket() {
+        let history = history_of(&[1, 9, 2, 3, 4, 8, 5, 6], 10);
+        assert_eq!(cpus(&history.downsample(4)), [9, 3, 8, 6]);
+        // Buckets of different sizes still cover all the samples
+        assert_eq!(cpus(&history.downsample(3)), [9, 4, 8]);
+        assert_eq!(cpus(&history.downsample(1)), [9]);
+        assert!(history.downsample(0).is_empty());
+    }
+
+    #[test]
+    fn downsample_all_the_fields() {
+        let mut history = History::default();
+        for (cpu, rss, disks_io) in [(50, 10, 0), (10, 30, 0), (20, 20, 0)] {
+            history.push(Sample { cpu, rss, disks_io }, 10);
+            history.set_latest_disks_io(u64::from(cpu) * 2);
+        }
+        let samples = history.downsample(1);
+        assert_eq!(
+            (samples[0].cpu, samples[0].rss, samples[0].disks_io),
+            (50, 30, 100)
+        );
+    }
+
+    #[test]
+    fn empty_history() {
+        let mut history = History::default();
+        assert!(history.is_empty());
+        assert!(history.latest().is_none());
+        assert!(history.downsample(80).is_empty());
+        history.set_latest_disks_io(1);
+        assert!(history.is_empty());
+    }
+}
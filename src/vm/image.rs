@@ -0,0 +1,63 @@
+//! Disk usage of the images: sparse raw images and qcow2 files use less space than their size
+
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::vm::helpers::format_bytes;
+
+/// First bytes of a qcow2 file
+const QCOW2_MAGIC: &[u8; 4] = b"QFI\xfb";
+
+/// Offset of the virtual size (big-endian u64) in the qcow2 header
+const QCOW2_SIZE_OFFSET: usize = 24;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImageUsage {
+    /// Size of the file (in bytes)
+    pub apparent: u64,
+    /// Size of the blocks allocated to the file (in bytes)
+    pub allocated: u64,
+    /// Size of the disk seen by the guest, for qcow2 images (in bytes)
+    pub virtual_size: Option<u64>,
+}
+
+impl ImageUsage {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|err| format!("Failed to access {}: {err}", path.display()))?;
+        Ok(ImageUsage {
+            apparent: metadata.len(),
+            // `st_blocks` is always in 512 bytes units
+            allocated: metadata.blocks() * 512,
+            virtual_size: qcow2_virtual_size(path),
+        })
+    }
+
+    /// Description of the usage, ie. "10G (1.2G allocated)" or "1.2G (256M allocated, 20G virtual)"
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} ({} allocated",
+            format_bytes(self.apparent),
+            format_bytes(self.allocated)
+        );
+        if let Some(virtual_size) = self.virtual_size {
+            summary.push_str(&format!(", {} virtual", format_bytes(virtual_size)));
+        }
+        summary.push(')');
+        summary
+    }
+}
+
+/// Virtual size of a qcow2 image, `None` if `path` isn't a qcow2 image
+fn qcow2_virtual_size(path: &Path) -> Option<u64> {
+    let mut header = [0u8; QCOW2_SIZE_OFFSET + 8];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if !header.starts_with(QCOW2_MAGIC) {
+        return None;
+    }
+    let size: [u8; 8] = header[QCOW2_SIZE_OFFSET..].try_into().ok()?;
+    Some(u64::from_be_bytes(size))
+}
@@ -1,4 +1,9 @@
 pub mod helpers;
+pub mod history;
+pub mod image;
+pub mod proc;
+pub mod qga;
+pub mod qmp;
 mod types;
 
-pub use types::{Vm, VmState};
+pub use types::{AttachedDisk, DEFAULT_MEM, OutputLine, SAVED_STATES_DIR, Vm, VmState};
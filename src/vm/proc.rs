@@ -0,0 +1,135 @@
+//! Helpers to read the statistics of the QEMU processes from `/proc`, and of their network
+//! interfaces from `/sys` (Linux only)
+
+use std::path::Path;
+
+/// Number of clock ticks per second, the unit of the CPU times in `/proc/<pid>/stat`
+pub fn clock_ticks_per_second() -> u64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        // This is the value on most Linux systems
+        _ => 100,
+    }
+}
+
+/// CPU time (user + system, in clock ticks) used by a process
+pub fn process_cpu_time(pid: u32) -> Option<u64> {
+    cpu_time(&format!("/proc/{pid}/stat"))
+}
+
+/// CPU time (user + system, in clock ticks) used by a thread of a process
+pub fn thread_cpu_time(pid: u32, tid: u32) -> Option<u64> {
+    cpu_time(&format!("/proc/{pid}/task/{tid}/stat"))
+}
+
+/// Reads the utime and stime fields (14th and 15th) of a `stat` file
+fn cpu_time(stat_file: &str) -> Option<u64> {
+    let stat = std::fs::read_to_string(stat_file).ok()?;
+    // The 2nd field (the command name) can contain spaces, so we only split what's after it
+    let (_, fields) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Resident memory (in bytes) of a process, from the `VmRSS` line of `/proc/<pid>/status`
+pub fn process_rss(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// Tap interface opened by a process, from the `iff:` line of the `/proc/<pid>/fdinfo/` file
+/// of its `/dev/net/tun` descriptor (only readable by the owner of the process)
+pub fn process_tap(pid: u32) -> Option<String> {
+    std::fs::read_dir(format!("/proc/{pid}/fdinfo"))
+        .ok()?
+        .flatten()
+        .find_map(|fdinfo| {
+            let fdinfo = std::fs::read_to_string(fdinfo.path()).ok()?;
+            fdinfo
+                .lines()
+                .find_map(|line| line.strip_prefix("iff:"))
+                .map(|iface| iface.trim().to_owned())
+        })
+}
+
+/// Traffic counters of a network interface
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetStats {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+}
+
+/// Reads the counters of `/sys/class/net/<iface>/statistics/`, `None` if the interface doesn't exist
+pub fn interface_stats(iface: &str) -> Option<NetStats> {
+    let counter = |name: &str| -> Option<u64> {
+        std::fs::read_to_string(format!("/sys/class/net/{iface}/statistics/{name}"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    };
+    Some(NetStats {
+        rx_bytes: counter("rx_bytes")?,
+        tx_bytes: counter("tx_bytes")?,
+        rx_packets: counter("rx_packets")?,
+        tx_packets: counter("tx_packets")?,
+    })
+}
+
+/// Number of online CPUs of the host
+pub fn host_cores() -> u32 {
+    match unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) } {
+        cores if cores > 0 => cores as u32,
+        _ => 1,
+    }
+}
+
+/// Memory of the host (in bytes), from `/proc/meminfo`
+#[derive(Clone, Copy, Debug)]
+pub struct HostMem {
+    pub total: u64,
+    /// Memory available to new processes without swapping
+    pub available: u64,
+}
+
+pub fn host_mem() -> Option<HostMem> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let kb: u64 = meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()?;
+        Some(kb * 1024)
+    };
+    Some(HostMem {
+        total: field("MemTotal:")?,
+        available: field("MemAvailable:")?,
+    })
+}
+
+/// Returns true if a process has opened `path`, from the links of `/proc/<pid>/fd/`.
+/// `None` if they can't be read (ie. when the process belongs to another user)
+pub fn process_has_open_file(pid: u32, path: &Path) -> Option<bool> {
+    let path = path.canonicalize().ok();
+    let fds = std::fs::read_dir(format!("/proc/{pid}/fd")).ok()?;
+    Some(
+        fds.flatten()
+            .any(|fd| std::fs::read_link(fd.path()).ok() == path),
+    )
+}
@@ -0,0 +1,223 @@
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::vm::qmp::QmpError;
+
+/// Timeout used when talking to a guest agent: it may not be running in the guest,
+/// in which case nobody will ever answer
+const QGA_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time given to the guest to power off after 'guest-shutdown'
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A file system of the guest, as reported by 'guest-get-fsinfo'
+#[derive(Clone, Debug)]
+pub struct GuestFilesystem {
+    pub mountpoint: String,
+    pub used_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+}
+
+/// Information reported by the guest agent
+#[derive(Clone, Debug, Default)]
+pub struct GuestInfo {
+    pub os: Option<String>,
+    pub hostname: Option<String>,
+    pub ip_addresses: Vec<String>,
+    pub filesystems: Vec<GuestFilesystem>,
+}
+
+/// A minimal QEMU guest agent client. The protocol is the same as QMP's
+/// except that there is no greeting nor capabilities negotiation.
+pub struct Qga {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Qga {
+    /// Connects to the guest agent socket `path` and synchronizes with the agent
+    pub fn connect(path: &Path) -> Result<Self, QmpError> {
+        let stream = UnixStream::connect(path).map_err(|err| {
+            QmpError::Io(format!(
+                "Failed to connect to the guest agent socket {}: {err}",
+                path.display()
+            ))
+        })?;
+        stream
+            .set_read_timeout(Some(QGA_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(QGA_TIMEOUT)))
+            .map_err(|err| {
+                QmpError::Io(format!("Failed to set guest agent socket timeouts: {err}"))
+            })?;
+        let writer = stream
+            .try_clone()
+            .map_err(|err| QmpError::Io(format!("Failed to clone guest agent socket: {err}")))?;
+
+        let mut qga = Qga {
+            reader: BufReader::new(stream),
+            writer,
+        };
+        qga.sync()?;
+        Ok(qga)
+    }
+
+    /// Executes `command` and returns the content of the "return" member of the response
+    pub fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value, QmpError> {
+        let mut request = json!({ "execute": command });
+        if let Some(arguments) = arguments {
+            request["arguments"] = arguments;
+        }
+
+        writeln!(self.writer, "{request}").map_err(|err| {
+            QmpError::Io(format!(
+                "Failed to send '{command}' to the guest agent: {err}"
+            ))
+        })?;
+
+        let message = self.read_message()?;
+        if let Some(value) = message.get("return") {
+            Ok(value.clone())
+        } else {
+            let error = &message["error"];
+            Err(QmpError::Command {
+                class: error["class"].as_str().unwrap_or("GenericError").to_owned(),
+                desc: error["desc"].as_str().unwrap_or("unknown error").to_owned(),
+            })
+        }
+    }
+
+    /// Checks that the agent answers
+    pub fn ping(&mut self) -> Result<(), QmpError> {
+        self.execute("guest-ping", None)?;
+        Ok(())
+    }
+
+    /// Gathers the information about the guest. Commands which aren't supported by the agent are ignored.
+    pub fn guest_info(&mut self) -> GuestInfo {
+        let mut info = GuestInfo::default();
+
+        if let Ok(osinfo) = self.execute("guest-get-osinfo", None) {
+            info.os = ["pretty-name", "name"]
+                .iter()
+                .find_map(|key| osinfo[key].as_str())
+                .map(|os| match osinfo["kernel-release"].as_str() {
+                    Some(release) => format!("{os} ({release})"),
+                    None => os.to_owned(),
+                });
+        }
+
+        if let Ok(hostname) = self.execute("guest-get-host-name", None) {
+            info.hostname = hostname["host-name"].as_str().map(str::to_owned);
+        }
+
+        if let Ok(interfaces) = self.execute("guest-network-get-interfaces", None) {
+            info.ip_addresses = interfaces
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|interface| !interface["name"].as_str().unwrap_or("").starts_with("lo"))
+                .flat_map(|interface| interface["ip-addresses"].as_array().into_iter().flatten())
+                .filter_map(|address| {
+                    Some(format!(
+                        "{}/{}",
+                        address["ip-address"].as_str()?,
+                        address["prefix"].as_u64()?
+                    ))
+                })
+                .collect();
+        }
+
+        if let Ok(filesystems) = self.execute("guest-get-fsinfo", None) {
+            info.filesystems = filesystems
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|filesystem| {
+                    Some(GuestFilesystem {
+                        mountpoint: filesystem["mountpoint"].as_str()?.to_owned(),
+                        used_bytes: filesystem["used-bytes"].as_u64(),
+                        total_bytes: filesystem["total-bytes"].as_u64(),
+                    })
+                })
+                .collect();
+        }
+
+        info
+    }
+
+    /// Asks the guest to power itself off and waits for QEMU to exit.
+    /// On success, the agent doesn't answer: the connection is closed when QEMU exits.
+    pub fn shutdown(&mut self) -> Result<(), QmpError> {
+        let request = json!({ "execute": "guest-shutdown", "arguments": { "mode": "powerdown" } });
+        writeln!(self.writer, "{request}").map_err(|err| {
+            QmpError::Io(format!(
+                "Failed to send 'guest-shutdown' to the guest agent: {err}"
+            ))
+        })?;
+        self.reader
+            .get_ref()
+            .set_read_timeout(Some(SHUTDOWN_TIMEOUT))
+            .map_err(|err| {
+                QmpError::Io(format!("Failed to set guest agent socket timeouts: {err}"))
+            })?;
+
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::ConnectionReset => Ok(()),
+            Ok(_) => {
+                let message: Value = serde_json::from_str(&line).map_err(|err| {
+                    QmpError::Io(format!("Failed to parse guest agent message: {err}"))
+                })?;
+                match message.get("error") {
+                    Some(error) => Err(QmpError::Command {
+                        class: error["class"].as_str().unwrap_or("GenericError").to_owned(),
+                        desc: error["desc"].as_str().unwrap_or("unknown error").to_owned(),
+                    }),
+                    None => Ok(()),
+                }
+            }
+            Err(err) => Err(QmpError::Io(format!(
+                "The guest didn't power off after {}s: {err}",
+                SHUTDOWN_TIMEOUT.as_secs()
+            ))),
+        }
+    }
+
+    /// Discards the responses to the commands sent by a previous client which may still be buffered
+    fn sync(&mut self) -> Result<(), QmpError> {
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64 % (1 << 31))
+            .unwrap_or(0);
+
+        writeln!(
+            self.writer,
+            "{}",
+            json!({ "execute": "guest-sync", "arguments": { "id": id } })
+        )
+        .map_err(|err| QmpError::Io(format!("Failed to send 'guest-sync': {err}")))?;
+
+        loop {
+            let message = self.read_message()?;
+            if message["return"].as_u64() == Some(id) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_message(&mut self) -> Result<Value, QmpError> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err(QmpError::Io("Guest agent connection closed".to_owned())),
+            Ok(_) => serde_json::from_str(&line)
+                .map_err(|err| QmpError::Io(format!("Failed to parse guest agent message: {err}"))),
+            Err(err) => Err(QmpError::Io(format!(
+                "The guest agent didn't answer: {err}"
+            ))),
+        }
+    }
+}
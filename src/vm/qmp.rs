@@ -0,0 +1,615 @@
+use serde_json::{Value, json};
+use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::vm::AttachedDisk;
+
+/// Timeout used when connecting, reading from and writing to a QMP socket
+const QMP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the guest has to release a disk being detached
+const DEVICE_DELETED_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Prefix of the ids of the disks attached by the TUI (ie. "tui-disk0")
+pub const ATTACHED_DISK_ID_PREFIX: &str = "tui-disk";
+
+/// Interval between two checks of the migration status when saving a VM state
+const MIGRATION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Predefined key combinations that can be sent to a VM (label, QEMU qcodes)
+pub const KEY_COMBINATIONS: &[(&str, &[&str])] = &[
+    ("Ctrl-Alt-Del", &["ctrl", "alt", "delete"]),
+    ("Ctrl-Alt-Esc (NetBSD DDB break)", &["ctrl", "alt", "esc"]),
+    ("Alt-SysRq-S (sync)", &["alt", "sysrq", "s"]),
+    ("Alt-SysRq-U (remount read-only)", &["alt", "sysrq", "u"]),
+    ("Alt-SysRq-B (reboot)", &["alt", "sysrq", "b"]),
+    ("SysRq", &["sysrq"]),
+];
+
+/// Hold times (in milliseconds) that can be used when sending keys. QEMU's default is 100ms.
+pub const HOLD_TIMES_MS: &[u32] = &[100, 250, 500, 1000, 2000];
+
+/// I/O counters of a block device, as reported by 'query-blockstats'
+#[derive(Clone, Debug)]
+pub struct BlockStats {
+    pub device: String,
+    pub rd_bytes: u64,
+    pub wr_bytes: u64,
+    pub rd_operations: u64,
+    pub wr_operations: u64,
+}
+
+/// A port forward of the user mode network stack, as listed by 'info usernet'
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortForward {
+    /// "tcp" or "udp"
+    pub protocol: String,
+    /// Empty when listening on all the host addresses
+    pub host_addr: String,
+    pub host_port: u16,
+    pub guest_addr: String,
+    pub guest_port: u16,
+}
+
+impl Display for PortForward {
+    /// Formats the port forward like the `hostfwd` parameter and the `hostfwd_add` command expect it
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}-{}:{}",
+            self.protocol, self.host_addr, self.host_port, self.guest_addr, self.guest_port
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum QmpError {
+    /// Connection, I/O or protocol error
+    Io(String),
+    /// Error returned by QEMU when executing a command
+    Command { class: String, desc: String },
+}
+
+impl Display for QmpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QmpError::Io(err) => write!(f, "{err}"),
+            QmpError::Command { class, desc } => write!(f, "{desc} ({class})"),
+        }
+    }
+}
+
+/// Where the QMP server of a VM listens
+#[derive(Clone, Debug, PartialEq)]
+pub enum QmpAddr {
+    /// TCP port on localhost (`qmp_port`)
+    Tcp(u16),
+    /// Unix socket (`qmp_socket`)
+    Unix(PathBuf),
+}
+
+impl Display for QmpAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QmpAddr::Tcp(port) => write!(f, "port {port}"),
+            QmpAddr::Unix(path) => write!(f, "socket {}", path.display()),
+        }
+    }
+}
+
+/// A minimal QMP client: it only handles one command at a time
+pub struct Qmp {
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: Box<dyn Write + Send>,
+    /// Asynchronous events received while waiting for a command response
+    events: Vec<Value>,
+}
+
+impl Qmp {
+    /// Connects to the QMP server listening on `addr` and negotiates the capabilities
+    pub fn connect(addr: &QmpAddr) -> Result<Self, QmpError> {
+        let (reader, writer) = connect_stream(addr)
+            .map_err(|err| QmpError::Io(format!("Failed to connect to QMP {addr}: {err}")))?;
+
+        let mut qmp = Qmp {
+            reader: BufReader::new(reader),
+            writer,
+            events: Vec::new(),
+        };
+
+        // The server starts by sending a greeting message
+        let greeting = qmp.read_message()?;
+        if greeting.get("QMP").is_none() {
+            return Err(QmpError::Io(format!("Unexpected QMP greeting: {greeting}")));
+        }
+
+        // We must leave the "capabilities negotiation" mode before sending any other command
+        qmp.execute("qmp_capabilities", None)?;
+
+        Ok(qmp)
+    }
+
+    /// Executes `command` and returns the content of the "return" member of the response
+    pub fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value, QmpError> {
+        let mut request = json!({ "execute": command });
+        if let Some(arguments) = arguments {
+            request["arguments"] = arguments;
+        }
+
+        writeln!(self.writer, "{request}")
+            .map_err(|err| QmpError::Io(format!("Failed to send '{command}': {err}")))?;
+
+        loop {
+            let message = self.read_message()?;
+            if let Some(value) = message.get("return") {
+                return Ok(value.clone());
+            } else if let Some(error) = message.get("error") {
+                return Err(QmpError::Command {
+                    class: error["class"].as_str().unwrap_or("GenericError").to_owned(),
+                    desc: error["desc"].as_str().unwrap_or("unknown error").to_owned(),
+                });
+            }
+            // Asynchronous events can be received at any time, we keep them for wait_event()
+            if message.get("event").is_some() {
+                self.events.push(message);
+            }
+        }
+    }
+
+    /// Sends a key combination, `keys` being QEMU qcodes (ie. "ctrl", "alt", "delete", ...).
+    /// QEMU queues the keys, so the combinations sent one after the other are typed in order.
+    pub fn send_keys(&mut self, keys: &[String], hold_time: u32) -> Result<(), QmpError> {
+        let keys: Vec<Value> = keys
+            .iter()
+            .map(|key| json!({ "type": "qcode", "data": key }))
+            .collect();
+        self.execute(
+            "send-key",
+            Some(json!({ "keys": keys, "hold-time": hold_time })),
+        )?;
+        Ok(())
+    }
+
+    /// Returns the current size (in bytes) of the memory balloon
+    pub fn query_balloon(&mut self) -> Result<u64, QmpError> {
+        let info = self.execute("query-balloon", None).map_err(balloon_error)?;
+        info["actual"]
+            .as_u64()
+            .ok_or_else(|| QmpError::Io(format!("Unexpected 'query-balloon' response: {info}")))
+    }
+
+    /// Asks the guest to resize its memory balloon to `bytes`
+    pub fn set_balloon(&mut self, bytes: u64) -> Result<(), QmpError> {
+        self.execute("balloon", Some(json!({ "value": bytes })))
+            .map_err(balloon_error)?;
+        Ok(())
+    }
+
+    /// Returns the I/O counters of all the block devices of the VM
+    pub fn query_blockstats(&mut self) -> Result<Vec<BlockStats>, QmpError> {
+        let devices = self.execute("query-blockstats", None)?;
+        let devices = devices.as_array().ok_or_else(|| {
+            QmpError::Io(format!("Unexpected 'query-blockstats' response: {devices}"))
+        })?;
+
+        Ok(devices
+            .iter()
+            .map(|device| {
+                // Disks attached with blockdev-add don't have a "device" name
+                let name = ["device", "qdev", "node-name"]
+                    .iter()
+                    .filter_map(|key| device[key].as_str())
+                    .find(|name| !name.is_empty())
+                    .unwrap_or("?");
+                let stats = &device["stats"];
+                BlockStats {
+                    device: name.to_owned(),
+                    rd_bytes: stats["rd_bytes"].as_u64().unwrap_or(0),
+                    wr_bytes: stats["wr_bytes"].as_u64().unwrap_or(0),
+                    rd_operations: stats["rd_operations"].as_u64().unwrap_or(0),
+                    wr_operations: stats["wr_operations"].as_u64().unwrap_or(0),
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the host thread id of each vCPU, ordered by vCPU index
+    pub fn query_vcpu_threads(&mut self) -> Result<Vec<u32>, QmpError> {
+        let cpus = self.execute("query-cpus-fast", None)?;
+        let mut threads: Vec<(u64, u32)> = cpus
+            .as_array()
+            .ok_or_else(|| QmpError::Io(format!("Unexpected 'query-cpus-fast' response: {cpus}")))?
+            .iter()
+            .filter_map(|cpu| {
+                Some((
+                    cpu["cpu-index"].as_u64()?,
+                    cpu["thread-id"].as_u64()? as u32,
+                ))
+            })
+            .collect();
+        threads.sort();
+        Ok(threads
+            .into_iter()
+            .map(|(_, thread_id)| thread_id)
+            .collect())
+    }
+
+    /// Executes a HMP (human monitor) command and returns its output
+    pub fn human_monitor_command(&mut self, command_line: &str) -> Result<String, QmpError> {
+        let output = self.execute(
+            "human-monitor-command",
+            Some(json!({ "command-line": command_line })),
+        )?;
+        Ok(output.as_str().unwrap_or_default().to_owned())
+    }
+
+    /// Returns the id of the user mode network backend and its port forwards
+    pub fn list_port_forwards(&mut self) -> Result<(Option<String>, Vec<PortForward>), QmpError> {
+        let output = self.human_monitor_command("info usernet")?;
+        Ok(parse_usernet(&output))
+    }
+
+    /// Adds a port forward on the user mode network backend `netdev`
+    pub fn add_port_forward(&mut self, netdev: Option<&str>, rule: &str) -> Result<(), QmpError> {
+        let command_line = match netdev {
+            Some(netdev) => format!("hostfwd_add {netdev} {rule}"),
+            None => format!("hostfwd_add {rule}"),
+        };
+        hmp_result(self.human_monitor_command(&command_line)?)
+    }
+
+    /// Removes a port forward from the user mode network backend `netdev`
+    pub fn remove_port_forward(
+        &mut self,
+        netdev: Option<&str>,
+        forward: &PortForward,
+    ) -> Result<(), QmpError> {
+        let rule = format!(
+            "{}:{}:{}",
+            forward.protocol, forward.host_addr, forward.host_port
+        );
+        let command_line = match netdev {
+            Some(netdev) => format!("hostfwd_remove {netdev} {rule}"),
+            None => format!("hostfwd_remove {rule}"),
+        };
+        hmp_result(self.human_monitor_command(&command_line)?)
+    }
+
+    /// Saves the whole state of the VM in `path` and stops QEMU.
+    /// The VM isn't stopped first: the migration pauses it at the end, and the saved state keeps
+    /// the "running" runstate, so QEMU resumes the VM by itself after `-incoming`.
+    pub fn save_state_and_quit(&mut self, path: &str) -> Result<(), QmpError> {
+        self.save_state(path)?;
+        self.execute("quit", None)?;
+        Ok(())
+    }
+
+    /// Migrates the VM state to the file `path` and waits for the migration to complete
+    fn save_state(&mut self, path: &str) -> Result<(), QmpError> {
+        self.execute("migrate", Some(json!({ "uri": format!("file:{path}") })))?;
+        loop {
+            let info = self.execute("query-migrate", None)?;
+            match info["status"].as_str() {
+                Some("completed") => return Ok(()),
+                Some("failed") | Some("cancelled") => {
+                    return Err(QmpError::Io(format!(
+                        "Failed to save the VM state: {}",
+                        info["error-desc"].as_str().unwrap_or("migration failed")
+                    )));
+                }
+                _ => std::thread::sleep(MIGRATION_POLL_INTERVAL),
+            }
+        }
+    }
+
+    /// Waits (at most `timeout`) for the asynchronous event `name` for which `matches(data)` is true
+    pub fn wait_event<F>(
+        &mut self,
+        name: &str,
+        timeout: Duration,
+        matches: F,
+    ) -> Result<(), QmpError>
+    where
+        F: Fn(&Value) -> bool,
+    {
+        let is_expected = |event: &Value| event["event"] == name && matches(&event["data"]);
+
+        if let Some(idx) = self.events.iter().position(is_expected) {
+            self.events.remove(idx);
+            return Ok(());
+        }
+
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            match self.read_message() {
+                Ok(message) if is_expected(&message) => return Ok(()),
+                Ok(message) => {
+                    if message.get("event").is_some() {
+                        self.events.push(message);
+                    }
+                }
+                // The read timed out, we check the deadline again
+                Err(QmpError::Io(_)) if Instant::now() < deadline => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(QmpError::Io(format!(
+            "Timed out waiting for the '{name}' event"
+        )))
+    }
+
+    /// Attaches the disk image `path` to the VM with `id` as block node name and device id
+    pub fn attach_disk(&mut self, id: &str, path: &str) -> Result<(), QmpError> {
+        let format = if path.ends_with(".qcow2") {
+            "qcow2"
+        } else {
+            "raw"
+        };
+        self.execute(
+            "blockdev-add",
+            Some(json!({
+                "node-name": id,
+                "driver": format,
+                "file": { "driver": "file", "filename": path },
+            })),
+        )?;
+        if let Err(err) = self.execute(
+            "device_add",
+            Some(json!({ "driver": "virtio-blk-pci", "id": id, "drive": id })),
+        ) {
+            // Don't leave an unused block node behind
+            let _ = self.execute("blockdev-del", Some(json!({ "node-name": id })));
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Returns the disks attached with `attach_disk()`, ie. by a previous run of the TUI
+    pub fn query_attached_disks(&mut self) -> Result<Vec<AttachedDisk>, QmpError> {
+        let devices = self.execute("query-block", None)?;
+        let devices = devices
+            .as_array()
+            .ok_or_else(|| QmpError::Io(format!("Unexpected 'query-block' response: {devices}")))?;
+
+        Ok(devices
+            .iter()
+            .filter_map(|device| {
+                let inserted = &device["inserted"];
+                let id = inserted["node-name"].as_str()?;
+                let image = Path::new(inserted["file"].as_str()?).file_name()?;
+                id.starts_with(ATTACHED_DISK_ID_PREFIX)
+                    .then(|| AttachedDisk {
+                        id: id.to_owned(),
+                        image: image.to_string_lossy().into_owned(),
+                    })
+            })
+            .collect())
+    }
+
+    /// Detaches the disk attached with `attach_disk()`
+    pub fn detach_disk(&mut self, id: &str) -> Result<(), QmpError> {
+        self.execute("device_del", Some(json!({ "id": id })))?;
+        // The device removal is asynchronous, the block node is in use until the guest released it
+        self.wait_event("DEVICE_DELETED", DEVICE_DELETED_TIMEOUT, |data| {
+            data["device"] == id
+        })?;
+        self.execute("blockdev-del", Some(json!({ "node-name": id })))?;
+        Ok(())
+    }
+
+    fn read_message(&mut self) -> Result<Value, QmpError> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err(QmpError::Io("QMP connection closed".to_owned())),
+            Ok(_) => serde_json::from_str(&line)
+                .map_err(|err| QmpError::Io(format!("Failed to parse QMP message: {err}"))),
+            Err(err) => Err(QmpError::Io(format!("Failed to read QMP message: {err}"))),
+        }
+    }
+}
+
+type Streams = (Box<dyn Read + Send>, Box<dyn Write + Send>);
+
+/// Connects to `addr`, returning the reading and the writing halves of the connection
+fn connect_stream(addr: &QmpAddr) -> std::io::Result<Streams> {
+    match addr {
+        QmpAddr::Tcp(port) => {
+            let stream = TcpStream::connect_timeout(
+                &SocketAddr::from(([127, 0, 0, 1], *port)),
+                QMP_TIMEOUT,
+            )?;
+            stream.set_read_timeout(Some(QMP_TIMEOUT))?;
+            stream.set_write_timeout(Some(QMP_TIMEOUT))?;
+            Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+        }
+        QmpAddr::Unix(path) => {
+            let stream = UnixStream::connect(path)?;
+            stream.set_read_timeout(Some(QMP_TIMEOUT))?;
+            stream.set_write_timeout(Some(QMP_TIMEOUT))?;
+            Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+        }
+    }
+}
+
+/// HMP commands don't fail, they print an error message instead.
+/// The only successful commands printing something are the 'hostfwd_remove' ones.
+fn hmp_result(output: String) -> Result<(), QmpError> {
+    let output = output.trim();
+    if output.is_empty()
+        || (output.starts_with("host forwarding rule for") && output.ends_with("removed"))
+    {
+        Ok(())
+    } else {
+        Err(QmpError::Io(output.to_owned()))
+    }
+}
+
+/// Parses the output of 'info usernet', which looks like this:
+/// ```text
+/// Hub -1 (net0):
+///   Protocol[State]    FD  Source Address  Port   Dest. Address  Port RecvQ SendQ
+///   TCP[HOST_FORWARD]  12               *  2222       10.0.2.15    22     0     0
+/// ```
+fn parse_usernet(output: &str) -> (Option<String>, Vec<PortForward>) {
+    let mut netdev = None;
+    let mut forwards = Vec::new();
+
+    for line in output.lines() {
+        let line = line.trim();
+        if line.ends_with("):") {
+            // "Hub -1 (net0):" or "VLAN -1 (user.0):" with older QEMU versions
+            if netdev.is_none() {
+                netdev = line
+                    .rsplit_once('(')
+                    .and_then(|(_, id)| id.strip_suffix("):"))
+                    .map(str::to_owned);
+            }
+        } else if let Some((protocol, _)) = line.split_once("[HOST_FORWARD]") {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if let [_, _, host_addr, host_port, guest_addr, guest_port, ..] = fields[..]
+                && let Ok(host_port) = host_port.parse()
+                && let Ok(guest_port) = guest_port.parse()
+            {
+                forwards.push(PortForward {
+                    protocol: protocol.to_lowercase(),
+                    host_addr: match host_addr {
+                        "*" => String::new(),
+                        host_addr => host_addr.to_owned(),
+                    },
+                    host_port,
+                    guest_addr: guest_addr.to_owned(),
+                    guest_port,
+                });
+            }
+        }
+    }
+
+    (netdev, forwards)
+}
+
+/// QEMU returns a "DeviceNotActive" error when the VM doesn't have a balloon device
+fn balloon_error(err: QmpError) -> QmpError {
+    match err {
+        QmpError::Command { class, .. } if class == "DeviceNotActive" => QmpError::Io(
+            "This VM has no balloon device (add '-device virtio-balloon' to its 'extra' parameter)"
+                .to_owned(),
+        ),
+        err => err,
+    }
+}
+
+/// Parses a free-form key sequence like "ctrl-alt-f2 r o o t ret" into key combinations,
+/// each one being QEMU qcodes
+pub fn parse_key_sequence(input: &str) -> Result<Vec<Vec<String>>, String> {
+    let combinations: Vec<Vec<String>> = input
+        .split_whitespace()
+        .map(|combination| combination.split('-').map(str::to_lowercase).collect())
+        .collect();
+    if combinations.is_empty() || combinations.iter().flatten().any(String::is_empty) {
+        Err(format!(
+            "invalid key sequence '{input}' (expected something like 'ctrl-alt-f2 ret')"
+        ))
+    } else {
+        Ok(combinations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forward(protocol: &str, host_addr: &str, host_port: u16, guest_port: u16) -> PortForward {
+        PortForward {
+            protocol: protocol.to_owned(),
+            host_addr: host_addr.to_owned(),
+            host_port,
+            guest_addr: "10.0.2.15".to_owned(),
+            guest_port,
+        }
+    }
+
+    #[test]
+    fn parse_usernet_without_forwards() {
+        let output = "Hub -1 (net0):\r\n  Protocol[State]    FD  Source Address  Port   Dest. Address  Port RecvQ SendQ\r\n";
+        assert_eq!(parse_usernet(output), (Some("net0".to_owned()), vec![]));
+        assert_eq!(parse_usernet(""), (None, vec![]));
+    }
+
+    #[test]
+    fn parse_usernet_forwards() {
+        let output = concat!(
+            "Hub -1 (net0):\r\n",
+            "  Protocol[State]    FD  Source Address  Port   Dest. Address  Port RecvQ SendQ\r\n",
+            "  TCP[HOST_FORWARD]  13               *  2222       10.0.2.15    22     0     0\r\n",
+            "  TCP[HOST_FORWARD]  12       127.0.0.1  8080       10.0.2.15    80     0     0\r\n",
+            "  UDP[HOST_FORWARD]  14               *  5353       10.0.2.15    53     0     0\r\n",
+            "  TCP[ESTABLISHED]   30       10.0.2.15 65532        10.0.2.2    22     0     0\r\n",
+            "  UDP[236 sec]       31       10.0.2.15   123        10.0.2.3   123     0     0\r\n",
+        );
+        assert_eq!(
+            parse_usernet(output),
+            (
+                Some("net0".to_owned()),
+                vec![
+                    forward("tcp", "", 2222, 22),
+                    forward("tcp", "127.0.0.1", 8080, 80),
+                    forward("udp", "", 5353, 53),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn parse_usernet_first_netdev() {
+        let output = concat!(
+            "VLAN -1 (user.0):\n",
+            "  TCP[HOST_FORWARD]  13               *  2222       10.0.2.15    22     0     0\n",
+            "VLAN -1 (user.1):\n",
+        );
+        let (netdev, forwards) = parse_usernet(output);
+        assert_eq!(netdev.as_deref(), Some("user.0"));
+        assert_eq!(forwards.len(), 1);
+    }
+
+    #[test]
+    fn parse_key_sequences() {
+        assert_eq!(
+            parse_key_sequence("ctrl-alt-f2"),
+            Ok(vec![vec![
+                "ctrl".to_owned(),
+                "alt".to_owned(),
+                "f2".to_owned()
+            ]])
+        );
+        assert_eq!(
+            parse_key_sequence("  Ctrl-Alt-F1   ret "),
+            Ok(vec![
+                vec!["ctrl".to_owned(), "alt".to_owned(), "f1".to_owned()],
+                vec!["ret".to_owned()]
+            ])
+        );
+        for input in ["", "   ", "ctrl-", "ctrl--c", "-ret", "ctrl-alt- del"] {
+            assert!(parse_key_sequence(input).is_err(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn hmp_results() {
+        assert!(hmp_result(String::new()).is_ok());
+        assert!(hmp_result("host forwarding rule for tcp::2222 removed\r\n".to_owned()).is_ok());
+        for error in [
+            "host forwarding rule for tcp::2222 not found\r\n",
+            "Could not set up host forwarding rule 'tcp::2222-:22'\r\n",
+            "invalid host forwarding rule 'tcp:2222' (Bad protocol name)\r\n",
+        ] {
+            match hmp_result(error.to_owned()) {
+                Err(QmpError::Io(message)) => assert_eq!(message, error.trim()),
+                res => panic!("{error:?}: {res:?}"),
+            }
+        }
+    }
+}
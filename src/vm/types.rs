@@ -6,19 +6,88 @@ use std::path::{Path, PathBuf};
 use libc::c_int;
 use ratatui::style::Color;
 
+use regex::Regex;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use crate::console;
 use crate::ui::{INVALID_CONF_VM_FG, RUNNING_VM_FG, STARTING_VM_FG, STOPPED_VM_FG, STOPPING_VM_FG};
 use crate::vm;
+use crate::vm::history::{History, Sample};
+use crate::vm::image::ImageUsage;
+use crate::vm::proc::NetStats;
+use crate::vm::qga::GuestInfo;
+use crate::vm::qmp::{BlockStats, QmpAddr};
+
+/// Directory (in the base directory) where the states of the hibernated VMs are saved
+pub const SAVED_STATES_DIR: &str = "states";
+
+/// Memory (in bytes) given by startnb.sh to the VMs without a `mem` parameter
+pub const DEFAULT_MEM: u64 = 256 << 20;
+
+/// Console output telling that a VM is ready, when `ready_pattern` isn't set:
+/// a NetBSD login prompt or a smolBSD service banner
+static DEFAULT_READY_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"login:|^smolBSD").unwrap());
 
 #[derive(Debug)]
 pub enum VmState {
-    InvalidConfiguration { cause: String },
+    InvalidConfiguration {
+        cause: String,
+    },
     Starting,
-    Running { pid: u32 },
+    /// QEMU is running but the readiness pattern hasn't been seen on the console yet
+    Booting {
+        pid: u32,
+    },
+    Running {
+        pid: u32,
+    },
     Stopping,
     StoppingToDelete,
     Stopped,
 }
 
+/// A disk hot-attached to a running VM
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttachedDisk {
+    /// QEMU device id, also used as the block node name
+    pub id: String,
+    /// Filename of the image in `images/`
+    pub image: String,
+}
+
+/// A line written by startnb.sh
+#[derive(Clone, Debug)]
+pub struct OutputLine {
+    /// True if the line was written on stderr
+    pub stderr: bool,
+    pub text: String,
+}
+
+/// I/O throughput of a block device, computed from two 'query-blockstats' samples
+#[derive(Clone, Debug, Default)]
+pub struct DiskIo {
+    pub device: String,
+    /// Bytes read per second
+    pub rd_bps: f64,
+    /// Bytes written per second
+    pub wr_bps: f64,
+    pub rd_iops: f64,
+    pub wr_iops: f64,
+}
+
+/// Network throughput of the tap interface of a bridged VM, computed from two samples
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetIo {
+    /// Bytes received per second (by the host, ie. sent by the guest)
+    pub rx_bps: f64,
+    /// Bytes transmitted per second (by the host, ie. received by the guest)
+    pub tx_bps: f64,
+    pub rx_pps: f64,
+    pub tx_pps: f64,
+}
+
 #[derive(Debug)]
 pub struct Vm {
     ///
@@ -35,12 +104,23 @@ pub struct Vm {
     ///
     /// Optional parameters
     ///
-    pub mem: Option<String>,
+    /// Configured memory size, in bytes
+    pub mem: Option<u64>,
     pub cores: Option<u8>,
     pub hostfwd: Option<String>,
     pub editprotect: bool,
     pub rmprotect: bool,
     pub qmp_port: Option<u16>,
+    /// QMP Unix socket, relative to the base directory (preferred over `qmp_port`)
+    pub qmp_socket: Option<String>,
+    /// QEMU guest agent Unix socket, relative to the base directory
+    pub qga_socket: Option<String>,
+    /// Serial console: a Unix socket relative to the base directory, or a PTY (ie. "/dev/pts/3")
+    pub serial_socket: Option<String>,
+    /// Regex matched against the console output to know when the VM is ready
+    pub ready_pattern: Option<Regex>,
+    /// Console script (in `scripts/<name>/`) run when the VM is ready
+    pub boot_script: Option<String>,
     pub bridgenet: Option<String>,
     pub share: Option<String>,
     pub sharerw: bool,
@@ -48,7 +128,72 @@ pub struct Vm {
 
     /// State
     pub state: VmState,
+    /// CPU usage (in %) of the QEMU process, relative to the `cores` of the VM
     pub cpu_usage: u8,
+    /// Last CPU time sample of the QEMU process, used to compute `cpu_usage`
+    last_cpu_time: Option<(Instant, u64)>,
+    /// Resident memory (in bytes) of the QEMU process
+    pub rss: Option<u64>,
+    /// CPU, memory and disks usage over the last `history_window`
+    pub history: History,
+    /// Disk usage of the image, updated by `State::refresh_images()`
+    pub img_usage: Option<ImageUsage>,
+    /// Current size of the memory balloon (in bytes), as reported by QMP
+    pub balloon: Option<u64>,
+    /// Disks attached while the VM is running
+    pub attached_disks: Vec<AttachedDisk>,
+    /// Set once the disks attached to the running VM have been queried, as they may have been
+    /// attached by a previous run of the TUI
+    pub attached_disks_queried: bool,
+    /// I/O throughput of each block device
+    pub disks_io: Vec<DiskIo>,
+    /// Last 'query-blockstats' sample, used to compute `disks_io`
+    last_blockstats: Option<(Instant, Vec<BlockStats>)>,
+    /// Tap interface of a bridged VM, found in the file descriptors of the QEMU process
+    pub tap: Option<String>,
+    /// PID of the QEMU process `tap` belongs to: the tap is looked up again when QEMU is restarted
+    tap_pid: Option<u32>,
+    /// Last counters of the tap interface, and their time
+    pub net_stats: Option<(Instant, NetStats)>,
+    /// Network throughput of the tap interface
+    pub net_io: Option<NetIo>,
+    /// True while a 'query-blockstats' sampling is in progress
+    pub blockstats_pending: bool,
+    /// True if the QMP Unix socket exists
+    pub qmp_socket_present: bool,
+    /// True if the VM has been hibernated (its state has been saved in `states/`)
+    pub saved_state: bool,
+    /// Did the guest agent answer the last ping? (`None` if it hasn't been pinged yet)
+    pub agent_available: Option<bool>,
+    /// Information reported by the guest agent
+    pub guest_info: Option<GuestInfo>,
+    /// True while the guest agent is being queried
+    pub agent_pending: bool,
+    /// Host thread id of each vCPU
+    pub vcpu_threads: Vec<u32>,
+    /// True while the vCPUs threads are being queried
+    pub vcpu_threads_pending: bool,
+    /// CPU usage (in %) of each vCPU
+    pub vcpus_usage: Vec<f64>,
+    /// Last CPU times sample of the vCPUs threads, used to compute `vcpus_usage`
+    last_vcpus_times: Option<(Instant, Vec<Option<u64>>)>,
+    /// When the VM was started from the TUI, until it is ready
+    pub boot_started: Option<Instant>,
+    /// Time it took for the VM to be ready, the last time it was started from the TUI
+    pub boot_duration: Option<Duration>,
+    /// Size of the console log already read by `check_ready()`, from the start marker written
+    /// when the VM was started by the TUI. `None` when the console isn't logged for this start
+    pub ready_log_offset: Option<u64>,
+    /// Whether QEMU logs the console of the running VM itself, `None` until it is known
+    /// (cf. `qemu_logs_console()`). Otherwise the TUI logs it while it runs.
+    pub console_logged_by_qemu: Option<bool>,
+    /// End of the console log read by `check_ready()` after its last newline (ie. a login prompt)
+    ready_log_partial: Vec<u8>,
+    /// Why QEMU is expected to exit (ie. "hibernated"), set when the VM is hibernated or shut down
+    /// from the guest while it keeps running until then
+    pub expected_exit: Option<String>,
+    /// Output of startnb.sh, the last time the VM was started from the TUI
+    pub start_log: Vec<OutputLine>,
 }
 
 impl Vm {
@@ -72,12 +217,47 @@ impl Vm {
             editprotect: false,
             rmprotect: false,
             qmp_port: None,
+            qmp_socket: None,
+            qga_socket: None,
+            serial_socket: None,
+            ready_pattern: None,
+            boot_script: None,
             bridgenet: None,
             share: None,
             sharerw: false,
             extra: None,
             state: VmState::Stopped,
             cpu_usage: 0,
+            last_cpu_time: None,
+            rss: None,
+            history: History::default(),
+            img_usage: None,
+            balloon: None,
+            attached_disks: Vec::new(),
+            attached_disks_queried: false,
+            disks_io: Vec::new(),
+            last_blockstats: None,
+            tap: None,
+            tap_pid: None,
+            net_stats: None,
+            net_io: None,
+            blockstats_pending: false,
+            qmp_socket_present: false,
+            saved_state: false,
+            agent_available: None,
+            guest_info: None,
+            agent_pending: false,
+            vcpu_threads: Vec::new(),
+            vcpu_threads_pending: false,
+            vcpus_usage: Vec::new(),
+            last_vcpus_times: None,
+            boot_started: None,
+            boot_duration: None,
+            ready_log_offset: None,
+            console_logged_by_qemu: None,
+            ready_log_partial: Vec::new(),
+            expected_exit: None,
+            start_log: Vec::new(),
         };
 
         // Convert vm_conf into a hashmap to check if it contains all the mandatory keys
@@ -93,7 +273,17 @@ impl Vm {
             match key {
                 "img" => res.img = Some(value.to_owned()),
                 "kernel" => res.kernel = Some(value.to_owned()),
-                "mem" => res.mem = Some(value.to_owned()),
+                "mem" => {
+                    res.mem = match vm::helpers::parse_mem(value) {
+                        Ok(value) => Some(value),
+                        Err(err) => {
+                            res.state = VmState::InvalidConfiguration {
+                                cause: format!("Failed to parse 'mem' parameter: {err}"),
+                            };
+                            break;
+                        }
+                    }
+                }
                 "cores" => {
                     res.cores = match value.parse() {
                         Ok(value) => Some(value),
@@ -134,7 +324,50 @@ impl Vm {
                         }
                     }
                 }
-                "qmp_port" => res.qmp_port = Some(value.parse().unwrap_or(0)),
+                "qmp_port" => {
+                    res.qmp_port = match value.trim_matches('"').parse::<u16>() {
+                        Ok(0) => {
+                            res.state = VmState::InvalidConfiguration {
+                                cause: "'qmp_port' parameter must be between 1 and 65535"
+                                    .to_owned(),
+                            };
+                            break;
+                        }
+                        Ok(value) => Some(value),
+                        Err(err) => {
+                            res.state = VmState::InvalidConfiguration {
+                                cause: format!(
+                                    "Failed to convert 'qmp_port' parameter ({value}) to a port number (1-65535): {err}"
+                                ),
+                            };
+                            break;
+                        }
+                    }
+                }
+                "qmp_socket" => {
+                    res.qmp_socket =
+                        Some(value.trim_matches('"').trim_start_matches("./").to_owned())
+                }
+                "qga_socket" => {
+                    res.qga_socket =
+                        Some(value.trim_matches('"').trim_start_matches("./").to_owned())
+                }
+                "serial_socket" => {
+                    res.serial_socket =
+                        Some(value.trim_matches('"').trim_start_matches("./").to_owned())
+                }
+                "ready_pattern" => match Regex::new(value.trim_matches('"')) {
+                    Ok(regex) => res.ready_pattern = Some(regex),
+                    Err(err) => {
+                        res.state = VmState::InvalidConfiguration {
+                            cause: format!(
+                                "Failed to parse 'ready_pattern' parameter ({value}): {err}"
+                            ),
+                        };
+                        break;
+                    }
+                },
+                "boot_script" => res.boot_script = Some(value.trim_matches('"').to_owned()),
                 "bridgenet" => res.bridgenet = Some(value.to_owned()),
                 "share" => res.share = Some(value.to_owned()),
                 "sharerw" => {
@@ -164,16 +397,49 @@ impl Vm {
                 ("Invalid configuration".to_owned(), INVALID_CONF_VM_FG)
             }
             VmState::Starting => ("Starting...".to_owned(), STARTING_VM_FG),
+            VmState::Booting { .. } => match self.boot_started {
+                Some(boot_started) => (
+                    format!("Booting ({}s)", boot_started.elapsed().as_secs()),
+                    STARTING_VM_FG,
+                ),
+                None => ("Booting".to_owned(), STARTING_VM_FG),
+            },
             VmState::Running { .. } => ("Running".to_owned(), RUNNING_VM_FG),
             VmState::Stopping => ("Stopping".to_owned(), STOPPING_VM_FG),
             VmState::StoppingToDelete => ("Stopping".to_owned(), STOPPING_VM_FG),
+            VmState::Stopped if self.saved_state => ("Hibernated".to_owned(), STOPPED_VM_FG),
             VmState::Stopped => ("Stopped".to_owned(), STOPPED_VM_FG),
         }
     }
 
+    /// File in which the state of the VM is saved when it's hibernated
+    pub fn saved_state_path(&self, base_directory: &str) -> PathBuf {
+        Path::new(base_directory)
+            .join(SAVED_STATES_DIR)
+            .join(format!("{}.state", self.name))
+    }
+
+    /// File from which the state of the VM is restored while it's resuming.
+    /// The saved state is renamed to this file so a resumed VM is never resumed twice from the same state.
+    pub fn resuming_state_path(&self, base_directory: &str) -> PathBuf {
+        Path::new(base_directory)
+            .join(SAVED_STATES_DIR)
+            .join(format!("{}.resuming", self.name))
+    }
+
     pub fn update_state(&mut self, base_directory: &str) {
+        self.qmp_socket_present = self
+            .qmp_socket
+            .as_ref()
+            .is_some_and(|qmp_socket| Path::new(base_directory).join(qmp_socket).exists());
+        self.saved_state = self.saved_state_path(base_directory).exists();
+
         match &self.state {
-            VmState::Starting | VmState::Running { .. } | VmState::Stopped | VmState::Stopping => {
+            VmState::Starting
+            | VmState::Booting { .. }
+            | VmState::Running { .. }
+            | VmState::Stopped
+            | VmState::Stopping => {
                 self.set_pid(base_directory);
             }
             // We don't do anything in those cases
@@ -183,7 +449,7 @@ impl Vm {
 
     pub fn kill(&mut self) -> Result<(), String> {
         match self.state {
-            VmState::Running { pid } => {
+            VmState::Booting { pid } | VmState::Running { pid } => {
                 let res: c_int;
                 let err_str: &str;
                 unsafe {
@@ -203,8 +469,437 @@ impl Vm {
         }
     }
 
+    /// Replaces the configuration of this VM with the one of `new_conf` (built from the modified
+    /// configuration file), keeping what we learned while the VM was running
+    pub fn update_conf(&mut self, new_conf: Vm) {
+        self.img = new_conf.img;
+        self.kernel = new_conf.kernel;
+        self.mem = new_conf.mem;
+        self.cores = new_conf.cores;
+        self.hostfwd = new_conf.hostfwd;
+        self.editprotect = new_conf.editprotect;
+        self.rmprotect = new_conf.rmprotect;
+        self.qmp_port = new_conf.qmp_port;
+        self.qmp_socket = new_conf.qmp_socket;
+        self.qga_socket = new_conf.qga_socket;
+        self.serial_socket = new_conf.serial_socket;
+        self.ready_pattern = new_conf.ready_pattern;
+        self.boot_script = new_conf.boot_script;
+        self.bridgenet = new_conf.bridgenet;
+        self.share = new_conf.share;
+        self.sharerw = new_conf.sharerw;
+        self.extra = new_conf.extra;
+
+        match (&self.state, new_conf.state) {
+            // The state computed when reading the new configuration is only relevant
+            // if the configuration is (or was) invalid
+            (_, state @ VmState::InvalidConfiguration { .. })
+            | (VmState::InvalidConfiguration { .. }, state) => self.state = state,
+            _ => {}
+        }
+    }
+
+    /// Where the QMP server of this VM listens, the Unix socket being preferred over the TCP port
+    pub fn qmp_addr(&self, base_directory: &str) -> Option<QmpAddr> {
+        match (&self.qmp_socket, self.qmp_port) {
+            (Some(qmp_socket), _) => {
+                Some(QmpAddr::Unix(Path::new(base_directory).join(qmp_socket)))
+            }
+            (None, Some(qmp_port)) => Some(QmpAddr::Tcp(qmp_port)),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns the reason why QMP commands can't be sent to this VM, if any
+    pub fn qmp_unavailable_reason(&self) -> Option<String> {
+        if self.qmp_port.is_none() && self.qmp_socket.is_none() {
+            Some(format!(
+                "QMP is not configured for this VM: add 'qmp_port=<port>' or 'qmp_socket=<path>' to etc/{}.conf and restart it",
+                self.name
+            ))
+        } else if !self.is_running() {
+            Some(format!("VM '{}' is not running", self.name))
+        } else {
+            None
+        }
+    }
+
+    /// Marks the VM as stopped and forgets everything we learned while it was running
+    pub fn set_stopped(&mut self) {
+        self.state = VmState::Stopped;
+        self.expected_exit = None;
+        self.ready_log_offset = None;
+        self.console_logged_by_qemu = None;
+        self.ready_log_partial.clear();
+        self.cpu_usage = 0;
+        self.last_cpu_time = None;
+        self.rss = None;
+        self.history.clear();
+        self.balloon = None;
+        self.attached_disks.clear();
+        self.attached_disks_queried = false;
+        self.disks_io.clear();
+        self.last_blockstats = None;
+        self.tap = None;
+        self.tap_pid = None;
+        self.net_stats = None;
+        self.net_io = None;
+        self.agent_available = None;
+        self.guest_info = None;
+        self.vcpu_threads.clear();
+        self.vcpus_usage.clear();
+        self.last_vcpus_times = None;
+    }
+
+    /// Computes the CPU usage of the VM from the CPU time of the QEMU process.
+    /// 100% means that all its cores are busy
+    pub fn sample_cpu(&mut self, time: Instant) {
+        let Some(cpu_time) = self.pid().and_then(vm::proc::process_cpu_time) else {
+            return;
+        };
+
+        if let Some((last_time, last_cpu_time)) = self.last_cpu_time {
+            let elapsed = time.duration_since(last_time).as_secs_f64();
+            if elapsed > 0.0 {
+                let ticks_per_second = vm::proc::clock_ticks_per_second() as f64;
+                let cores = self.cores.unwrap_or(1).max(1) as f64;
+                self.cpu_usage = (cpu_time.saturating_sub(last_cpu_time) as f64
+                    / ticks_per_second
+                    / elapsed
+                    / cores
+                    * 100.0)
+                    .round()
+                    .min(100.0) as u8;
+            }
+        }
+        self.last_cpu_time = Some((time, cpu_time));
+    }
+
+    /// Adds the current resource usage to the history, which keeps `capacity` samples.
+    /// The disks throughput of the sample is set by `update_disks_io()`, when the 'query-blockstats'
+    /// sent at the same tick answers.
+    pub fn record_history(&mut self, capacity: usize) {
+        self.history.push(
+            Sample {
+                cpu: self.cpu_usage,
+                rss: self.rss.unwrap_or(0),
+                disks_io: 0,
+            },
+            capacity,
+        );
+    }
+
+    /// Reads the resident memory of the QEMU process
+    pub fn sample_rss(&mut self) {
+        self.rss = self.pid().and_then(vm::proc::process_rss);
+    }
+
+    /// Reads the counters of the tap interface of a bridged VM and computes its throughput
+    pub fn sample_net(&mut self, time: Instant) {
+        let Some(pid) = self.pid().filter(|_| self.bridgenet.is_some()) else {
+            return;
+        };
+
+        // The tap is recreated when QEMU is restarted, possibly with another name
+        let mut stats = self.tap.as_deref().and_then(vm::proc::interface_stats);
+        if self.tap_pid != Some(pid) || stats.is_none() {
+            self.tap = vm::proc::process_tap(pid);
+            self.tap_pid = Some(pid);
+            self.net_stats = None;
+            self.net_io = None;
+            stats = self.tap.as_deref().and_then(vm::proc::interface_stats);
+        }
+        let Some(stats) = stats else {
+            return;
+        };
+
+        if let Some((last_time, last_stats)) = self.net_stats {
+            let elapsed = time.duration_since(last_time).as_secs_f64();
+            if elapsed > 0.0 {
+                let rate =
+                    |current: u64, previous: u64| current.saturating_sub(previous) as f64 / elapsed;
+                self.net_io = Some(NetIo {
+                    rx_bps: rate(stats.rx_bytes, last_stats.rx_bytes),
+                    tx_bps: rate(stats.tx_bytes, last_stats.tx_bytes),
+                    rx_pps: rate(stats.rx_packets, last_stats.rx_packets),
+                    tx_pps: rate(stats.tx_packets, last_stats.tx_packets),
+                });
+            }
+        }
+        self.net_stats = Some((time, stats));
+    }
+
+    /// Computes the CPU usage of each vCPU from the CPU time of its thread
+    pub fn sample_vcpus(&mut self, time: Instant) {
+        let Some(pid) = self.pid() else {
+            return;
+        };
+        let times: Vec<Option<u64>> = self
+            .vcpu_threads
+            .iter()
+            .map(|tid| vm::proc::thread_cpu_time(pid, *tid))
+            .collect();
+
+        if let Some((last_time, last_times)) = &self.last_vcpus_times {
+            let elapsed = time.duration_since(*last_time).as_secs_f64();
+            if elapsed > 0.0 {
+                let ticks_per_second = vm::proc::clock_ticks_per_second() as f64;
+                self.vcpus_usage = times
+                    .iter()
+                    .zip(last_times)
+                    .map(|(current, previous)| match (current, previous) {
+                        (Some(current), Some(previous)) => {
+                            (current.saturating_sub(*previous) as f64 / ticks_per_second / elapsed
+                                * 100.0)
+                                .min(100.0)
+                        }
+                        _ => 0.0,
+                    })
+                    .collect();
+            }
+        }
+        self.last_vcpus_times = Some((time, times));
+    }
+
+    /// Path of the guest agent socket
+    pub fn qga_path(&self, base_directory: &str) -> Option<PathBuf> {
+        self.qga_socket
+            .as_ref()
+            .map(|qga_socket| Path::new(base_directory).join(qga_socket))
+    }
+
+    /// Returns the reason why the guest agent can't be used, if any
+    pub fn agent_unavailable_reason(&self) -> Option<String> {
+        if self.qga_socket.is_none() {
+            Some(format!(
+                "The guest agent is not configured for this VM: add 'qga_socket=<path>' to etc/{}.conf",
+                self.name
+            ))
+        } else if !self.is_running() {
+            Some(format!("VM '{}' is not running", self.name))
+        } else if self.agent_available != Some(true) {
+            Some("The guest agent doesn't answer (is qemu-ga running in the guest?)".to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// Path of the serial console (Unix socket or PTY)
+    pub fn serial_path(&self, base_directory: &str) -> Option<PathBuf> {
+        self.serial_socket
+            .as_ref()
+            .map(|serial_socket| Path::new(base_directory).join(serial_socket))
+    }
+
+    /// QEMU arguments exporting the serial console on `serial_socket` and logging its output
+    /// in `logs/<name>.log`. `None` when there is no serial socket, when it is a PTY
+    /// (which has to be configured in `extra`) or when `extra` already has a `-serial` option.
+    /// startnb.sh doesn't add a serial port when it daemonizes QEMU (`-d`), so this one is the
+    /// first UART of the VM, the one used as its console.
+    pub fn serial_chardev_args(&self, base_directory: &str) -> Option<String> {
+        let path = self.serial_path(base_directory)?;
+        if path.starts_with("/dev") || self.extra_has_serial() {
+            return None;
+        }
+        Some(format!(
+            "-chardev socket,id=tui-serial,path={},server=on,wait=off,logfile={},logappend=on -serial chardev:tui-serial",
+            path.display(),
+            console::log::log_path(base_directory, &self.name).display()
+        ))
+    }
+
+    /// QEMU arguments exporting QMP on `qmp_socket` (startnb.sh only knows `qmp_port`).
+    /// `None` when there is no QMP socket or when `extra` already exports QMP on a Unix socket.
+    pub fn qmp_socket_args(&self, base_directory: &str) -> Option<String> {
+        let qmp_socket = self.qmp_socket.as_ref()?;
+        let extra_has_qmp_socket = self.extra.as_deref().is_some_and(|extra| {
+            extra
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .windows(2)
+                .any(|args| args[0] == "-qmp" && args[1].starts_with("unix:"))
+        });
+        if extra_has_qmp_socket {
+            return None;
+        }
+        Some(format!(
+            "-qmp unix:{},server=on,wait=off",
+            Path::new(base_directory).join(qmp_socket).display()
+        ))
+    }
+
+    /// Returns true if the `extra` parameter configures a serial port
+    fn extra_has_serial(&self) -> bool {
+        self.extra
+            .as_deref()
+            .is_some_and(|extra| extra.split_whitespace().any(|arg| arg == "-serial"))
+    }
+
+    /// Returns whether QEMU logs the console of this running VM itself, ie. if it was started
+    /// by the TUI with `serial_chardev_args()`. `None` if it can't be known
+    pub fn qemu_logs_console(&self, base_directory: &str) -> Option<bool> {
+        if self.serial_chardev_args(base_directory).is_none() {
+            return Some(false);
+        }
+        let log_path = console::log::log_path(base_directory, &self.name);
+        vm::proc::process_has_open_file(self.pid()?, &log_path)
+    }
+
+    /// Returns why the console log of this VM is missing or incomplete, if it is
+    pub fn console_log_notice(&self, base_directory: &str) -> Option<String> {
+        let reason = match &self.serial_socket {
+            None => return Some("The console isn't logged: 'serial_socket' isn't set".to_owned()),
+            Some(_) if self.extra_has_serial() => "the serial port is configured in 'extra'",
+            Some(_) if self.serial_chardev_args(base_directory).is_none() => {
+                "'serial_socket' is a PTY"
+            }
+            Some(_) if self.qemu_logs_console(base_directory) == Some(false) => {
+                "the VM wasn't started by the TUI"
+            }
+            Some(_) => return None,
+        };
+        Some(format!(
+            "The console is only logged while the TUI runs: {reason}"
+        ))
+    }
+
+    /// Returns the reason why the serial console can't be used, if any
+    pub fn console_unavailable_reason(&self) -> Option<String> {
+        if self.serial_socket.is_none() {
+            Some(format!(
+                "The serial console is not configured for this VM: add 'serial_socket=<path>' to etc/{}.conf",
+                self.name
+            ))
+        } else if !self.is_running() {
+            Some(format!("VM '{}' is not running", self.name))
+        } else {
+            None
+        }
+    }
+
+    /// Forgets the I/O throughput and the last sample, so that the next rates aren't computed over
+    /// the time 'query-blockstats' failed
+    pub fn clear_disks_io(&mut self) {
+        self.disks_io.clear();
+        self.last_blockstats = None;
+    }
+
+    /// Computes the I/O throughput of each block device from the previous sample
+    pub fn update_disks_io(&mut self, time: Instant, stats: Vec<BlockStats>) {
+        if let Some((last_time, last_stats)) = &self.last_blockstats {
+            let elapsed = time.duration_since(*last_time).as_secs_f64();
+            if elapsed > 0.0 {
+                let rate =
+                    |current: u64, previous: u64| current.saturating_sub(previous) as f64 / elapsed;
+                self.disks_io = stats
+                    .iter()
+                    .filter_map(|current| {
+                        let previous = last_stats
+                            .iter()
+                            .find(|previous| previous.device == current.device)?;
+                        Some(DiskIo {
+                            device: current.device.clone(),
+                            rd_bps: rate(current.rd_bytes, previous.rd_bytes),
+                            wr_bps: rate(current.wr_bytes, previous.wr_bytes),
+                            rd_iops: rate(current.rd_operations, previous.rd_operations),
+                            wr_iops: rate(current.wr_operations, previous.wr_operations),
+                        })
+                    })
+                    .collect();
+                let disks_io = self.total_disks_io().map_or(0.0, |(rd, wr)| rd + wr);
+                self.history.set_latest_disks_io(disks_io as u64);
+            }
+        }
+        self.last_blockstats = Some((time, stats));
+    }
+
+    /// Total I/O throughput of all the block devices (read, written) in bytes per second
+    pub fn total_disks_io(&self) -> Option<(f64, f64)> {
+        if self.disks_io.is_empty() {
+            None
+        } else {
+            Some(self.disks_io.iter().fold((0.0, 0.0), |(rd, wr), disk| {
+                (rd + disk.rd_bps, wr + disk.wr_bps)
+            }))
+        }
+    }
+
+    /// Path of the disk image
+    pub fn img_path(&self, base_directory: &str) -> Option<PathBuf> {
+        self.img
+            .as_ref()
+            .map(|img| Path::new(base_directory).join(img.trim_matches('"')))
+    }
+
+    /// Returns true if `image` (a filename in `images/`) is this VM's disk or one of its attached disks
+    pub fn uses_image(&self, image: &str) -> bool {
+        self.img
+            .as_deref()
+            .and_then(|img| Path::new(img.trim_matches('"')).file_name())
+            .is_some_and(|img| img == image)
+            || self.attached_disks.iter().any(|disk| disk.image == image)
+    }
+
+    /// Returns true if the QEMU process of this VM is running (the guest may still be booting)
     pub fn is_running(&self) -> bool {
-        matches!(self.state, VmState::Running { .. })
+        matches!(
+            self.state,
+            VmState::Booting { .. } | VmState::Running { .. }
+        )
+    }
+
+    /// PID of the QEMU process
+    pub fn pid(&self) -> Option<u32> {
+        match self.state {
+            VmState::Booting { pid } | VmState::Running { pid } => Some(pid),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the QEMU process of this VM still exists
+    pub fn is_process_alive(&self) -> bool {
+        // Sending the signal 0 only checks if the process exists
+        self.pid()
+            .is_some_and(|pid| unsafe { libc::kill(pid as i32, 0) == 0 })
+    }
+
+    /// Switches a booting VM to `VmState::Running` once its readiness pattern appeared in its console log.
+    /// Returns true if the VM has just become ready
+    pub fn check_ready(&mut self, base_directory: &str) -> bool {
+        let VmState::Booting { pid } = self.state else {
+            return false;
+        };
+        let regex = self
+            .ready_pattern
+            .as_ref()
+            .unwrap_or(&DEFAULT_READY_PATTERN);
+        let log_path = console::log::log_path(base_directory, &self.name);
+        let Some(offset) = self.ready_log_offset.as_mut() else {
+            return false;
+        };
+        let Ok(data) = console::log::read_appended(&log_path, offset) else {
+            return false;
+        };
+        self.ready_log_partial.extend_from_slice(&data);
+        let ready = String::from_utf8_lossy(&self.ready_log_partial)
+            .lines()
+            .any(|line| regex.is_match(&console::log::clean_line(line)));
+        // Only the last line is kept, the next read may complete it
+        if let Some(idx) = self
+            .ready_log_partial
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+        {
+            self.ready_log_partial.drain(..=idx);
+        }
+
+        if ready {
+            self.state = VmState::Running { pid };
+            self.boot_duration = self.boot_started.take().map(|started| started.elapsed());
+            self.ready_log_offset = None;
+            self.ready_log_partial.clear();
+        }
+        ready
     }
 
     pub fn set_pid(&mut self, base_directory: &str) {
@@ -214,8 +909,13 @@ impl Vm {
                 if let Ok(res) = std::fs::exists(&pid_file)
                     && res == true
                 {
+                    // The readiness of a VM can only be watched if its console is logged since it
+                    // was started by the TUI, and a resumed VM is ready as soon as it runs
+                    let watch_boot = self.ready_log_offset.is_some()
+                        && !self.resuming_state_path(base_directory).exists();
                     self.state = match read_to_string(&pid_file) {
                         Ok(res) => match res.trim().parse() {
+                            Ok(res) if watch_boot => VmState::Booting { pid: res },
                             Ok(res) => VmState::Running { pid: res },
                             Err(err) => VmState::InvalidConfiguration {
                                 cause: format!("Failed to parse pid file {pid_file}: {err}"),
@@ -233,3 +933,72 @@ impl Vm {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn stats(device: &str, rd_bytes: u64, wr_bytes: u64, ops: u64) -> BlockStats {
+        BlockStats {
+            device: device.to_owned(),
+            rd_bytes,
+            wr_bytes,
+            rd_operations: ops,
+            wr_operations: ops,
+        }
+    }
+
+    #[test]
+    fn disks_io_needs_two_samples() {
+        let mut vm = Vm::new(vec![], &PathBuf::from("etc/myvm.conf"));
+        vm.update_disks_io(Instant::now(), vec![stats("virtio0", 1000, 1000, 10)]);
+        assert!(vm.disks_io.is_empty());
+        assert_eq!(vm.total_disks_io(), None);
+    }
+
+    #[test]
+    fn disks_io_rates() {
+        let mut vm = Vm::new(vec![], &PathBuf::from("etc/myvm.conf"));
+        let start = Instant::now();
+        vm.update_disks_io(
+            start,
+            vec![stats("virtio0", 1000, 0, 10), stats("virtio1", 0, 0, 0)],
+        );
+        vm.update_disks_io(
+            start + Duration::from_secs(2),
+            vec![
+                stats("virtio0", 5000, 2000, 30),
+                stats("virtio1", 100, 300, 2),
+                // Appeared since the previous sample
+                stats("tui-disk0", 100, 100, 1),
+            ],
+        );
+
+        assert_eq!(vm.disks_io.len(), 2);
+        let disk = &vm.disks_io[0];
+        assert_eq!(disk.device, "virtio0");
+        assert_eq!((disk.rd_bps, disk.wr_bps), (2000.0, 1000.0));
+        assert_eq!((disk.rd_iops, disk.wr_iops), (10.0, 10.0));
+        assert_eq!(vm.total_disks_io(), Some((2050.0, 1150.0)));
+    }
+
+    #[test]
+    fn disks_io_after_a_counter_reset() {
+        let mut vm = Vm::new(vec![], &PathBuf::from("etc/myvm.conf"));
+        let start = Instant::now();
+        vm.update_disks_io(start, vec![stats("virtio0", 5000, 5000, 50)]);
+        // ie. the device has been detached and attached again
+        vm.update_disks_io(
+            start + Duration::from_secs(1),
+            vec![stats("virtio0", 100, 0, 1)],
+        );
+        assert_eq!(vm.total_disks_io(), Some((0.0, 0.0)));
+
+        vm.update_disks_io(
+            start + Duration::from_secs(2),
+            vec![stats("virtio0", 600, 0, 2)],
+        );
+        assert_eq!(vm.total_disks_io(), Some((500.0, 0.0)));
+    }
+}